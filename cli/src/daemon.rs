@@ -0,0 +1,104 @@
+use savegame_manager_core::{profile::SavegameManagerProfile, screenshot, BackupEngine, EngineEvent};
+
+use std::{path::Path, process::ExitCode, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+
+use serde::Serialize;
+
+/// Same interval the GUI timer ticks in
+const TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct DaemonEvent<'a> {
+    profile: &'a str,
+    event: &'static str,
+    message: &'a str,
+}
+
+fn report(profile: &str, event: &'static str, message: &str, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(&DaemonEvent { profile, event, message }).unwrap_or_default());
+    } else {
+        println!("[{}] {}: {}", profile, event, message);
+    }
+}
+
+fn report_backup_created(engine: &BackupEngine, name: &str, json: bool) {
+    report(&engine.profile().name, "backup_created", name, json);
+    for pruned in engine.last_prune_report().removed {
+        report(&engine.profile().name, "backup_pruned", &format!("{} ({})", pruned.name, pruned.reason), json);
+    }
+}
+
+/// Watches all given profiles at once until SIGINT or SIGTERM is received. Every profile gets
+/// its own [`BackupEngine`], so backups are classified and rotated exactly like in the GUI.
+/// On shutdown each engine handles its exit save the same way the GUI does when it is closed.
+pub fn run_daemon(profiles: Vec<SavegameManagerProfile>, json: bool) -> Result<ExitCode, anyhow::Error> {
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone())?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone())?;
+
+    let mut engines: Vec<BackupEngine> = vec![];
+    for mut profile in profiles {
+        if profile.src_path.is_empty() || profile.dst_path.is_empty() {
+            report(&profile.name, "skipped", "Source or destination path is empty", json);
+            continue;
+        }
+        if !Path::new(&profile.src_path).is_dir() || !Path::new(&profile.dst_path).is_dir() {
+            report(&profile.name, "skipped", "Source or destination folder does not exist", json);
+            continue;
+        }
+        // Otherwise every backup would report a failed screenshot
+        profile.screenshots = profile.screenshots && screenshot::is_supported();
+
+        let mut engine = BackupEngine::new(profile);
+        for name in engine.clean_up_staging() {
+            report(&engine.profile().name, "cleaned_up", &name, json);
+        }
+        if !engine.start_watcher() {
+            report(&engine.profile().name, "watcher_failed", "Could not start folder monitoring", json);
+            continue;
+        }
+        if let Err(err) = engine.refresh_backups() {
+            report(&engine.profile().name, "backup_failed", &format!("Error reading backups: {}", err), json);
+        } else if engine.find_live_backup().is_none() {
+            engine.start_backup(false);
+        }
+
+        report(&engine.profile().name, "watching", &engine.profile().src_path, json);
+        engines.push(engine);
+    }
+
+    if engines.is_empty() {
+        return Err(anyhow::anyhow!("No profile could be watched"));
+    }
+
+    while !stop.load(Ordering::Relaxed) {
+        for engine in &engines {
+            match engine.tick() {
+                Some(EngineEvent::BackupCreated(name)) => report_backup_created(engine, &name, json),
+                Some(EngineEvent::BackupFailed(error)) => report(&engine.profile().name, "backup_failed", &error, json),
+                Some(EngineEvent::ScreenshotFailed(error)) => report(&engine.profile().name, "screenshot_failed", &error, json),
+                Some(EngineEvent::WatcherHealthChanged(health)) => report(&engine.profile().name, "watcher_health", &health.to_string(), json),
+                None => {},
+            }
+        }
+        std::thread::sleep(TICK_INTERVAL);
+    }
+
+    // Let backups that are still running finish before the exit saves are dealt with
+    while engines.iter().any(|e| e.is_busy()) {
+        for engine in engines.iter().filter(|e| e.is_busy()) {
+            if let Some(EngineEvent::BackupCreated(name)) = engine.tick() {
+                report_backup_created(engine, &name, json);
+            }
+        }
+        std::thread::sleep(TICK_INTERVAL);
+    }
+
+    for engine in &mut engines {
+        engine.shutdown();
+        report(&engine.profile().name, "stopped", "", json);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
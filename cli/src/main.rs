@@ -0,0 +1,573 @@
+mod daemon;
+
+use savegame_manager_core::{backup::{self, BackupAction, SavegameMeta}, diff, hashcache::HashCache, hexdiff, local_datetime_from_millis, profile::{self, SavegameManagerProfile}, repair, retention::PruneReport, storage::StorageMode, watcher::WatcherBackend};
+
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+/// The operation failed
+const EXIT_FAILURE: u8 = 1;
+/// `verify` found backups that do not match their checksums
+const EXIT_VERIFY_FAILED: u8 = 3;
+
+/// Manage savegame backups without the graphical interface.
+///
+/// Paths are taken from a profile in the config file, or given explicitly with `--src` and
+/// `--dst`. Explicit paths override the paths of the profile.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Config file to read profiles from
+    #[arg(long, global = true, default_value = profile::DATA_FILE)]
+    config: String,
+
+    /// Name of the profile to use. Defaults to the profile selected in the GUI.
+    #[arg(short, long, global = true)]
+    profile: Option<String>,
+
+    /// Savegame folder to back up
+    #[arg(long, global = true)]
+    src: Option<String>,
+
+    /// Folder the backups are stored in
+    #[arg(long, global = true)]
+    dst: Option<String>,
+
+    /// Glob pattern of files that belong to the savegame, replaces the patterns of the profile
+    #[arg(long, global = true)]
+    include: Vec<String>,
+
+    /// Glob pattern of files to leave alone, replaces the patterns of the profile
+    #[arg(long, global = true)]
+    exclude: Vec<String>,
+
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a backup of the savegame folder
+    Backup {
+        /// Kind of backup to create
+        #[arg(long, value_enum, default_value_t = BackupKind::Detect)]
+        kind: BackupKind,
+        /// Store file contents deduplicated, regardless of the profile's storage mode
+        #[arg(long)]
+        deduplicate: bool,
+        /// Store the backup as a single archive, regardless of the profile's storage mode
+        #[arg(long, value_enum, conflicts_with = "deduplicate")]
+        archive: Option<ArchiveFormat>,
+    },
+    /// List all backups, newest first
+    List {
+        /// Only list backups whose name, notes or tags contain this text. `#tag` only lists
+        /// backups with exactly that tag.
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Restore a backup into the savegame folder
+    Restore {
+        backup: String,
+        /// Only restore this file and leave the other live files alone. Can be given multiple times
+        #[arg(long = "file")]
+        files: Vec<String>,
+    },
+    /// Undo the last restore by restoring the snapshot taken before it
+    UndoRestore,
+    /// Rename a backup
+    Rename {
+        old_name: String,
+        new_name: String,
+    },
+    /// Set notes and tags of a backup
+    Annotate {
+        backup: String,
+        /// Free-form notes. Keeps the current notes if omitted.
+        #[arg(long)]
+        notes: Option<String>,
+        /// Comma separated list of tags. Keeps the current tags if omitted.
+        #[arg(long)]
+        tags: Option<String>,
+    },
+    /// Pin a backup, so rotation and pruning never delete it
+    Pin {
+        backup: String,
+    },
+    /// Unpin a backup, so it is rotated and pruned like any other backup again
+    Unpin {
+        backup: String,
+    },
+    /// Delete a backup
+    Delete {
+        backup: String,
+        /// Delete permanently instead of moving the backup to the recycle bin
+        #[arg(long)]
+        permanent: bool,
+    },
+    /// Check backups against their stored checksums
+    Verify {
+        /// Backup to verify. Verifies all backups if omitted.
+        backup: Option<String>,
+    },
+    /// Show which files were added, removed or changed between two backups
+    Diff {
+        /// Older side of the comparison
+        old: String,
+        /// Newer side of the comparison. Compares against the live files if omitted.
+        new: Option<String>,
+        /// Also list files that did not change
+        #[arg(long)]
+        all: bool,
+        /// Compare this file byte by byte and show the changed bytes as hex
+        #[arg(long)]
+        file: Option<String>,
+        /// Unchanged lines of 16 bytes shown around every change with `--file`
+        #[arg(long, default_value_t = 2)]
+        context: usize,
+        /// Maximum number of lines shown with `--file`. `0` shows every changed line
+        #[arg(long, default_value_t = 2000)]
+        max_lines: usize,
+    },
+    /// Delete temporary saves, autosaves exceeding the profile's maximum and backups not
+    /// covered by the profile's retention policy
+    Prune {
+        /// Number of autosaves to keep. Defaults to the profile's setting.
+        #[arg(long)]
+        max_autosaves: Option<u16>,
+    },
+    /// Rewrite meta files of backups taken by older versions in the current format
+    UpgradeMeta,
+    /// Rebuild missing or unreadable meta files from the files of the backups. Folders copied
+    /// into the destination by hand are adopted as backups
+    Repair {
+        /// Only repair this backup. Defaults to every backup without a readable meta file
+        backup: Option<String>,
+        /// Only list the backups that would be repaired
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Watch all profiles of the config file at once and back them up until stopped
+    Daemon {
+        /// How changes are noticed, regardless of the profile's setting
+        #[arg(long, value_enum)]
+        watcher: Option<WatcherKind>,
+        /// Milliseconds between two scans when polling
+        #[arg(long)]
+        poll_interval: Option<u32>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackupKind {
+    /// Decide like the GUI does by comparing against the latest backup
+    Detect,
+    Keep,
+    Auto,
+    Temp,
+    Manual,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ArchiveFormat {
+    Zip,
+    TarZst,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum WatcherKind {
+    Native,
+    Polling,
+    Hybrid,
+}
+
+#[derive(Serialize)]
+struct BackupInfo {
+    name: String,
+    date: i64,
+    kind: backup::BackupKind,
+    checksums: Vec<(String, String)>,
+    pinned: bool,
+    notes: String,
+    tags: Vec<String>,
+}
+
+impl From<&SavegameMeta> for BackupInfo {
+    fn from(meta: &SavegameMeta) -> Self {
+        Self {
+            name: meta.name.clone(),
+            date: meta.date,
+            kind: meta.kind,
+            checksums: meta.checksums.clone(),
+            pinned: meta.pinned,
+            notes: meta.notes.clone(),
+            tags: meta.tags.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BackupResult {
+    name: Option<String>,
+    pruned: PruneReport,
+}
+
+#[derive(Serialize)]
+struct VerifyResult {
+    name: String,
+    ok: bool,
+    #[serde(flatten)] report: backup::VerifyReport,
+}
+
+fn resolve_profile(cli: &Cli) -> Result<SavegameManagerProfile, anyhow::Error> {
+    let mut profile = match &cli.profile {
+        Some(name) => {
+            let profiles = profile::load_profiles(&cli.config)?;
+            match profile::find_profile(&profiles, name) {
+                Some(p) => p.clone(),
+                None => return Err(anyhow::anyhow!("Profile {} not found in {}", name, cli.config)),
+            }
+        },
+        None => {
+            if cli.src.is_some() && cli.dst.is_some() {
+                SavegameManagerProfile::default()
+            } else {
+                let profiles = profile::load_profiles(&cli.config)?;
+                match profiles.iter().find(|p| p.selected).or(profiles.first()) {
+                    Some(p) => p.clone(),
+                    None => return Err(anyhow::anyhow!("No profile found in {}", cli.config)),
+                }
+            }
+        },
+    };
+
+    if let Some(src) = &cli.src {
+        profile.src_path = src.clone();
+    }
+    if let Some(dst) = &cli.dst {
+        profile.dst_path = dst.clone();
+    }
+    if !cli.include.is_empty() {
+        profile.include_patterns = cli.include.clone();
+    }
+    if !cli.exclude.is_empty() {
+        profile.exclude_patterns = cli.exclude.clone();
+    }
+
+    Ok(profile)
+}
+
+fn find_backup(profile: &SavegameManagerProfile, name: &String) -> Result<SavegameMeta, anyhow::Error> {
+    backup::get_meta_for_backup(&profile.dst_path, name).map_err(|err| anyhow::anyhow!("Backup {} could not be read: {}", name, err))
+}
+
+fn sorted_backups(profile: &SavegameManagerProfile) -> Result<Vec<SavegameMeta>, anyhow::Error> {
+    let mut backups = backup::look_for_backups(&profile.dst_path)?;
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.date));
+    Ok(backups)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), anyhow::Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<ExitCode, anyhow::Error> {
+    if let Command::Daemon { watcher, poll_interval } = &cli.command {
+        let mut profiles = if cli.profile.is_some() || (cli.src.is_some() && cli.dst.is_some()) {
+            vec![resolve_profile(cli)?]
+        } else {
+            profile::load_profiles(&cli.config)?
+        };
+        for profile in &mut profiles {
+            match watcher {
+                Some(WatcherKind::Native) => profile.watcher_backend = WatcherBackend::Native,
+                Some(WatcherKind::Polling) => profile.watcher_backend = WatcherBackend::Polling,
+                Some(WatcherKind::Hybrid) => profile.watcher_backend = WatcherBackend::Hybrid,
+                None => {},
+            }
+            if let Some(poll_interval) = poll_interval {
+                profile.poll_interval_millis = *poll_interval;
+            }
+        }
+        return daemon::run_daemon(profiles, cli.json);
+    }
+
+    let mut profile = resolve_profile(cli)?;
+    let mut hash_cache = HashCache::open(&profile.dst_path);
+
+    match &cli.command {
+        Command::Backup { kind, deduplicate, archive } => {
+            if *deduplicate {
+                profile.storage_mode = StorageMode::Deduplicated;
+            }
+            match archive {
+                Some(ArchiveFormat::Zip) => profile.storage_mode = StorageMode::Zip,
+                Some(ArchiveFormat::TarZst) => profile.storage_mode = StorageMode::TarZst,
+                None => {},
+            }
+
+            let action = match kind {
+                BackupKind::Detect => {
+                    let live_hashes = backup::create_hash_list(&profile.src_path, &profile.file_filter(), &mut hash_cache)?;
+                    backup::classify_backup(&profile, &live_hashes, backup::latest_non_temp(&sorted_backups(&profile)?).as_ref())
+                },
+                BackupKind::Keep => BackupAction::Keep,
+                BackupKind::Auto => BackupAction::Auto,
+                BackupKind::Temp => BackupAction::Temp,
+                BackupKind::Manual => BackupAction::Manual,
+            };
+
+            let outcome = backup::run_backup_action(action, &profile, None)?;
+            if cli.json {
+                print_json(&BackupResult { name: outcome.name, pruned: outcome.pruned })?;
+            } else {
+                match outcome.name {
+                    Some(name) => println!("Created backup {}", name),
+                    None => println!("Nothing changed since the latest backup"),
+                }
+                for pruned in &outcome.pruned.removed {
+                    println!("Deleted backup {} ({})", pruned.name, pruned.reason);
+                }
+            }
+        },
+        Command::List { filter } => {
+            let mut backups = sorted_backups(&profile)?;
+            if let Some(filter) = filter {
+                backups.retain(|b| b.matches_filter(filter));
+            }
+            if cli.json {
+                print_json(&backups.iter().map(BackupInfo::from).collect::<Vec<_>>())?;
+            } else {
+                for backup in &backups {
+                    let mut line = format!("{}\t{}\t{}", local_datetime_from_millis(backup.date).format("%c"), backup.kind, backup.name);
+                    if backup.pinned {
+                        line.push_str("\t(pinned)");
+                    }
+                    if !backup.tags.is_empty() {
+                        line.push_str(&format!("\t[{}]", backup.tags.join(", ")));
+                    }
+                    println!("{}", line);
+                    if !backup.notes.is_empty() {
+                        println!("\t{}", backup.notes.replace('\n', "\n\t"));
+                    }
+                }
+            }
+        },
+        Command::Restore { backup: name, files } => {
+            let meta = find_backup(&profile, name)?;
+            if files.is_empty() {
+                backup::load_backup(&profile.src_path, &profile.dst_path, &meta, &profile.file_filter(), &mut hash_cache)?;
+            } else {
+                backup::load_backup_files(&profile.src_path, &profile.dst_path, &meta, files, &mut hash_cache)?;
+            }
+            if cli.json {
+                print_json(&BackupInfo::from(&meta))?;
+            } else if files.is_empty() {
+                println!("Restored backup {}", name);
+            } else {
+                println!("Restored {} from backup {}", files.join(", "), name);
+            }
+        },
+        Command::UndoRestore => {
+            let snapshot = backup::undo_last_restore(&profile.src_path, &profile.dst_path, &profile.file_filter(), &mut hash_cache)?;
+            if cli.json {
+                print_json(&BackupInfo::from(&snapshot))?;
+            } else {
+                println!("Restored snapshot {}", snapshot.name);
+            }
+        },
+        Command::Rename { old_name, new_name } => {
+            find_backup(&profile, old_name)?;
+            backup::rename_backup(&profile.dst_path, old_name, new_name)?;
+            if cli.json {
+                print_json(new_name)?;
+            } else {
+                println!("Renamed backup {} to {}", old_name, new_name);
+            }
+        },
+        Command::Annotate { backup: name, notes, tags } => {
+            let meta = find_backup(&profile, name)?;
+            let notes = notes.clone().unwrap_or(meta.notes);
+            let tags = tags.as_ref().map(|t| backup::parse_tags(t)).unwrap_or(meta.tags);
+            let meta = backup::set_annotations(&profile.dst_path, name, &notes, &tags).map_err(|err| anyhow::anyhow!("Backup {} could not be updated: {}", name, err))?;
+            if cli.json {
+                print_json(&BackupInfo::from(&meta))?;
+            } else {
+                println!("Updated notes and tags of backup {}", name);
+            }
+        },
+        Command::Pin { backup: name } | Command::Unpin { backup: name } => {
+            let pinned = matches!(cli.command, Command::Pin { .. });
+            let meta = backup::set_pinned(&profile.dst_path, name, pinned).map_err(|err| anyhow::anyhow!("Backup {} could not be updated: {}", name, err))?;
+            if cli.json {
+                print_json(&BackupInfo::from(&meta))?;
+            } else {
+                println!("{} backup {}", if pinned { "Pinned" } else { "Unpinned" }, name);
+            }
+        },
+        Command::Delete { backup: name, permanent } => {
+            find_backup(&profile, name)?;
+            if *permanent {
+                backup::delete_backup(&profile.dst_path, name)?;
+            } else {
+                backup::recycle_backup(&profile.dst_path, name)?;
+            }
+            if cli.json {
+                print_json(name)?;
+            } else {
+                println!("Deleted backup {}", name);
+            }
+        },
+        Command::Diff { old, new, file: Some(file), context, max_lines, .. } => {
+            let old = find_backup(&profile, old)?;
+            let options = hexdiff::HexDiffOptions { context_lines: *context, max_lines: *max_lines };
+            let result = match new {
+                Some(new) => hexdiff::diff_backup_files(&profile.dst_path, &old, &find_backup(&profile, new)?, file, &options)?,
+                None => hexdiff::diff_file_with_live(&profile.src_path, &profile.dst_path, &old, file, &options)?,
+            };
+
+            if cli.json {
+                print_json(&result)?;
+            } else {
+                print!("{}", result);
+            }
+        },
+        Command::Diff { old, new, all, file: None, .. } => {
+            let old = find_backup(&profile, old)?;
+            let mut result = match new {
+                Some(new) => diff::diff_backups(&profile.dst_path, &old, &find_backup(&profile, new)?)?,
+                None => diff::diff_with_live(&profile.src_path, &profile.dst_path, &old, &profile.file_filter(), &mut hash_cache)?,
+            };
+            if !*all {
+                result.files.retain(|f| f.change != diff::FileChange::Unchanged);
+            }
+
+            if cli.json {
+                print_json(&result)?;
+            } else {
+                if *all {
+                    for file in result.files.iter().filter(|f| f.change == diff::FileChange::Unchanged) {
+                        println!("  {}", file.path);
+                    }
+                }
+                println!("{}", result);
+            }
+        },
+        Command::Verify { backup: name } => {
+            let backups = match name {
+                Some(name) => vec![find_backup(&profile, name)?],
+                None => sorted_backups(&profile)?,
+            };
+
+            let mut results: Vec<VerifyResult> = vec![];
+            for meta in &backups {
+                let report = backup::verify_backup(&profile.dst_path, meta)?;
+                results.push(VerifyResult { name: meta.name.clone(), ok: report.is_ok(), report });
+            }
+
+            if cli.json {
+                print_json(&results)?;
+            } else {
+                for result in &results {
+                    println!("{}\t{}", if result.ok { "OK" } else { "FAILED" }, result.name);
+                    for file in &result.report.missing {
+                        println!("\tmissing\t{}", file);
+                    }
+                    for file in &result.report.corrupted {
+                        println!("\tcorrupted\t{}", file);
+                    }
+                    for file in &result.report.extra {
+                        println!("\textra\t{}", file);
+                    }
+                }
+            }
+
+            if results.iter().any(|r| !r.ok) {
+                return Ok(ExitCode::from(EXIT_VERIFY_FAILED));
+            }
+        },
+        Command::Prune { max_autosaves } => {
+            let report = backup::prune_backups(&profile.dst_path, &max_autosaves.unwrap_or(profile.auto_saves_max), &profile.retention);
+            if cli.json {
+                print_json(&report)?;
+            } else {
+                for pruned in &report.removed {
+                    println!("Deleted backup {} ({})", pruned.name, pruned.reason);
+                }
+            }
+        },
+        Command::UpgradeMeta => {
+            let upgraded = backup::upgrade_meta_files(&profile.dst_path)?;
+            if cli.json {
+                print_json(&upgraded)?;
+            } else {
+                for name in &upgraded {
+                    println!("Upgraded backup {}", name);
+                }
+            }
+        },
+        Command::Repair { backup: name, dry_run } => {
+            let mut broken = repair::find_broken_backups(&profile.dst_path)?;
+            if let Some(name) = name {
+                broken.retain(|b| b.name == *name);
+                if broken.is_empty() {
+                    return Err(anyhow::anyhow!("Backup {} does not need a repair", name));
+                }
+            }
+
+            if *dry_run {
+                if cli.json {
+                    print_json(&broken)?;
+                } else {
+                    for backup in &broken {
+                        println!("{}\t{}", backup.name, backup.error);
+                    }
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let mut report = repair::RepairReport::default();
+            for backup in broken {
+                match repair::repair_backup(&profile.dst_path, &backup.name) {
+                    Ok(meta) => report.repaired.push(meta.name),
+                    Err(err) => report.failed.push(repair::BrokenBackup { name: backup.name, error: err.to_string() }),
+                }
+            }
+            if cli.json {
+                print_json(&report)?;
+            } else {
+                for name in &report.repaired {
+                    println!("Repaired backup {}", name);
+                }
+                for failed in &report.failed {
+                    eprintln!("Could not repair backup {}: {}", failed.name, failed.error);
+                }
+            }
+            if !report.failed.is_empty() {
+                return Ok(ExitCode::from(EXIT_FAILURE));
+            }
+        },
+        Command::Daemon { .. } => unreachable!(),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
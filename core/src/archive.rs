@@ -0,0 +1,248 @@
+use crate::*;
+use storage::StorageMode;
+
+use std::{fs::File, io::{Read, Write}, path::{Path, PathBuf}};
+
+/// zstd compression level used for tar.zst archives
+const ZSTD_LEVEL: i32 = 9;
+
+/// File extension of backups stored with the given storage mode, if they are archives.
+pub fn extension(storage: &StorageMode) -> Option<&'static str> {
+    match storage {
+        StorageMode::Zip => Some("zip"),
+        StorageMode::TarZst => Some("tar.zst"),
+        StorageMode::Folder | StorageMode::Deduplicated => None,
+    }
+}
+
+/// Storage mode of the backup archive at `path`, judged by its extension.
+pub fn storage_for_path(path: &Path) -> Option<StorageMode> {
+    let file_name = path.file_name().unwrap_or_default().to_str().unwrap_or_default();
+    for storage in [StorageMode::Zip, StorageMode::TarZst] {
+        if file_name.ends_with(&format!(".{}", extension(&storage).unwrap_or_default())) {
+            return Some(storage);
+        }
+    }
+    None
+}
+
+/// Returns the backup name if `file_name` is the name of a backup archive.
+pub fn backup_name_from_archive(file_name: &str) -> Option<String> {
+    for storage in [StorageMode::Zip, StorageMode::TarZst] {
+        if let Some(name) = file_name.strip_suffix(&format!(".{}", extension(&storage).unwrap_or_default())) {
+            if !name.is_empty() {
+                return Some(name.to_owned());
+            }
+        }
+    }
+    None
+}
+
+/// Path of the archive of the backup called `backup_name`, if the backup is stored as archive.
+pub fn find_archive(dst_path: &String, backup_name: &String) -> Option<PathBuf> {
+    for storage in [StorageMode::Zip, StorageMode::TarZst] {
+        let path = PathBuf::from(dst_path).join(format!("{}.{}", backup_name, extension(&storage).unwrap_or_default()));
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn zip_options() -> zip::write::SimpleFileOptions {
+    zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated)
+}
+
+fn append_tar_meta<W: Write>(tar: &mut tar::Builder<W>, meta: &[u8]) -> Result<(), std::io::Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(meta.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, "meta.json", meta)
+}
+
+/// Writes `files` (pairs of the name inside the archive and the file on disk) and the given
+/// `meta.json` content into a new archive at `path`.
+pub fn write_archive(path: &PathBuf, storage: &StorageMode, files: &Vec<(String, PathBuf)>, meta: &[u8]) -> Result<(), anyhow::Error> {
+    let file = File::create(path)?;
+
+    let file = match storage {
+        StorageMode::Zip => {
+            let mut zip = zip::ZipWriter::new(file);
+            let options = zip_options();
+            for (name, file_path) in files {
+                zip.start_file(name.as_str(), options)?;
+                std::io::copy(&mut File::open(file_path)?, &mut zip)?;
+            }
+            zip.start_file("meta.json", options)?;
+            zip.write_all(meta)?;
+            zip.finish()?
+        },
+        StorageMode::TarZst => {
+            let mut tar = tar::Builder::new(zstd::stream::write::Encoder::new(file, ZSTD_LEVEL)?);
+            for (name, file_path) in files {
+                tar.append_path_with_name(file_path, name)?;
+            }
+            append_tar_meta(&mut tar, meta)?;
+            tar.into_inner()?.finish()?
+        },
+        StorageMode::Folder | StorageMode::Deduplicated => {
+            return Err(anyhow::anyhow!("{} is not an archive format", storage));
+        },
+    };
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Replaces `meta.json` in the archive at `path`. The archive is rewritten to `temp_path` first
+/// and moved over the original once it is complete.
+pub fn replace_meta(path: &PathBuf, temp_path: &PathBuf, meta: &[u8]) -> Result<(), anyhow::Error> {
+    match rewrite_with_meta(path, temp_path, meta).and_then(|_| Ok(std::fs::rename(temp_path, path)?)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            std::fs::remove_file(temp_path).unwrap_or_default();
+            Err(err)
+        }
+    }
+}
+
+fn rewrite_with_meta(path: &PathBuf, temp_path: &PathBuf, meta: &[u8]) -> Result<(), anyhow::Error> {
+    let file = File::create(temp_path)?;
+
+    let file = match storage_for_path(path) {
+        Some(StorageMode::Zip) => {
+            let mut source = zip::ZipArchive::new(File::open(path)?)?;
+            let mut zip = zip::ZipWriter::new(file);
+            for i in 0..source.len() {
+                let entry = source.by_index_raw(i)?;
+                if entry.name() != "meta.json" {
+                    zip.raw_copy_file(entry)?;
+                }
+            }
+            zip.start_file("meta.json", zip_options())?;
+            zip.write_all(meta)?;
+            zip.finish()?
+        },
+        Some(StorageMode::TarZst) => {
+            let mut source = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(path)?)?);
+            let mut tar = tar::Builder::new(zstd::stream::write::Encoder::new(file, ZSTD_LEVEL)?);
+            for entry in source.entries()? {
+                let mut entry = entry?;
+                if entry.path()?.to_str() != Some("meta.json") {
+                    let header = entry.header().clone();
+                    tar.append(&header, &mut entry)?;
+                }
+            }
+            append_tar_meta(&mut tar, meta)?;
+            tar.into_inner()?.finish()?
+        },
+        _ => return Err(anyhow::anyhow!("{} is not a backup archive", path.display())),
+    };
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Reads a single file from the archive at `path`. Returns `None` if the archive does not
+/// contain a file called `name`.
+pub fn read_file(path: &PathBuf, name: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let mut content: Vec<u8> = vec![];
+    match storage_for_path(path) {
+        Some(StorageMode::Zip) => {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+            let result = zip.by_name(name);
+            match result {
+                Ok(mut entry) => { entry.read_to_end(&mut content)?; },
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        },
+        Some(StorageMode::TarZst) => {
+            let mut tar = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(path)?)?);
+            let mut found = false;
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                if entry.path()?.to_str() == Some(name) {
+                    entry.read_to_end(&mut content)?;
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Ok(None);
+            }
+        },
+        _ => return Err(anyhow::anyhow!("{} is not a backup archive", path.display())),
+    }
+    Ok(Some(content))
+}
+
+/// Name, size in bytes and modification time (unix milliseconds) of every file in the archive
+/// at `path`, without extracting it.
+pub fn list_files(path: &PathBuf) -> Result<Vec<(String, u64, Option<i64>)>, anyhow::Error> {
+    let mut files: Vec<(String, u64, Option<i64>)> = vec![];
+    match storage_for_path(path) {
+        Some(StorageMode::Zip) => {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+            for i in 0..zip.len() {
+                let entry = zip.by_index_raw(i)?;
+                if entry.is_dir() {
+                    continue;
+                }
+                // Zip archives store the local time without a time zone
+                let modified = entry.last_modified().and_then(|date| {
+                    chrono::NaiveDate::from_ymd_opt(date.year() as i32, date.month() as u32, date.day() as u32)
+                        .and_then(|day| day.and_hms_opt(date.hour() as u32, date.minute() as u32, date.second() as u32))
+                        .and_then(|date| date.and_local_timezone(chrono::Local).earliest())
+                        .map(|date| date.timestamp_millis())
+                });
+                files.push((entry.name().to_owned(), entry.size(), modified));
+            }
+        },
+        Some(StorageMode::TarZst) => {
+            let mut tar = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(path)?)?);
+            for entry in tar.entries()? {
+                let entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let modified = entry.header().mtime().ok().map(|seconds| seconds as i64 * 1000);
+                files.push((entry.path()?.to_string_lossy().into_owned(), entry.size(), modified));
+            }
+        },
+        _ => return Err(anyhow::anyhow!("{} is not a backup archive", path.display())),
+    }
+    Ok(files)
+}
+
+/// Extracts every file of the archive at `path` into the folder `target`.
+pub fn extract(path: &PathBuf, target: &PathBuf) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(target)?;
+    match storage_for_path(path) {
+        Some(StorageMode::Zip) => {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i)?;
+                if entry.is_dir() {
+                    continue;
+                }
+                // Entries with absolute paths or `..` are skipped instead of written outside of target
+                if let Some(name) = entry.enclosed_name() {
+                    let out_path = target.join(name);
+                    if let Some(parent) = out_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::io::copy(&mut entry, &mut File::create(out_path)?)?;
+                }
+            }
+        },
+        Some(StorageMode::TarZst) => {
+            let mut tar = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(path)?)?);
+            tar.unpack(target)?;
+        },
+        _ => return Err(anyhow::anyhow!("{} is not a backup archive", path.display())),
+    }
+    Ok(())
+}
@@ -0,0 +1,1227 @@
+use crate::*;
+use filter::FileFilter;
+use hashcache::HashCache;
+use storage::StorageMode;
+
+use trash::delete;
+use std::{fs::File, io::Write, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+
+/// What a backup was taken for. It decides how rotation and pruning treat the backup, the name
+/// is only a label.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// The live files had nothing in common with the previous backup.
+    #[default]
+    Keep,
+    /// Taken periodically while the files keep changing. Rotated by the autosave maximum.
+    Auto,
+    /// Taken shortly after another backup. Replaced by the next backup.
+    Temp,
+    /// The last temporary save before the application was closed.
+    Exit,
+    /// Requested explicitly by the user.
+    Manual,
+    /// Snapshot of the live files taken automatically before a backup was restored.
+    Prerestore,
+}
+
+impl std::fmt::Display for BackupKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Keep => "Kept",
+            Self::Auto => "Autosave",
+            Self::Temp => "Temporary",
+            Self::Exit => "Exit save",
+            Self::Manual => "Manual",
+            Self::Prerestore => "Pre-restore",
+        })
+    }
+}
+
+impl BackupKind {
+    /// Kind of a backup written before the kind was stored, guessed from its name.
+    pub fn from_name(name: &str) -> Self {
+        if name.starts_with("temp_") {
+            BackupKind::Temp
+        } else if name.starts_with("auto_") {
+            BackupKind::Auto
+        } else if name.starts_with("manual_") {
+            BackupKind::Manual
+        } else if name.starts_with("exit_") {
+            BackupKind::Exit
+        } else if name.starts_with("prerestore_") {
+            BackupKind::Prerestore
+        } else {
+            BackupKind::Keep
+        }
+    }
+}
+
+/// Content of a backup's `meta.json`. Older files are upgraded by [`schema::parse_meta`] when
+/// they are read, so every field is required here.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavegameMeta {
+    #[serde(skip)] pub name: String,
+    /// Schema version the backup was read with. See [`schema::META_SCHEMA_VERSION`].
+    #[serde(skip)] pub stored_schema_version: u32,
+    pub schema_version: u32,
+    pub date: i64,
+    pub kind: BackupKind,
+    pub checksums: Vec<(String, String)>,
+    pub storage: StorageMode,
+    /// Pinned backups are never removed by rotation or pruning.
+    pub pinned: bool,
+    /// Free-form description of the backup.
+    pub notes: String,
+    pub tags: Vec<String>,
+}
+
+impl Default for SavegameMeta {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            stored_schema_version: schema::META_SCHEMA_VERSION,
+            schema_version: schema::META_SCHEMA_VERSION,
+            date: Default::default(),
+            kind: Default::default(),
+            checksums: Default::default(),
+            storage: Default::default(),
+            pinned: Default::default(),
+            notes: Default::default(),
+            tags: Default::default(),
+        }
+    }
+}
+
+impl SavegameMeta {
+    /// Whether the backup's `meta.json` was written with an older schema version.
+    pub fn needs_upgrade(&self) -> bool {
+        self.stored_schema_version < self.schema_version
+    }
+
+    pub fn is_temp(&self) -> bool {
+        self.kind == BackupKind::Temp
+    }
+    pub fn is_auto(&self) -> bool {
+        self.kind == BackupKind::Auto
+    }
+    /// Snapshot of the live files taken automatically before a backup was restored.
+    pub fn is_prerestore(&self) -> bool {
+        self.kind == BackupKind::Prerestore
+    }
+
+    /// Whether the backup matches a filter typed by the user. `#tag` only matches backups with
+    /// exactly that tag, any other text is searched for in the name, the notes and the tags.
+    /// Both ignore case.
+    pub fn matches_filter(&self, filter: &str) -> bool {
+        let filter = filter.trim().to_lowercase();
+        if filter.is_empty() {
+            return true;
+        }
+
+        match filter.strip_prefix('#') {
+            Some(tag) => self.tags.iter().any(|t| t.to_lowercase() == tag.trim()),
+            None => {
+                self.name.to_lowercase().contains(&filter)
+                    || self.notes.to_lowercase().contains(&filter)
+                    || self.tags.iter().any(|t| t.to_lowercase().contains(&filter))
+            },
+        }
+    }
+}
+
+/// Splits a comma separated list of tags as entered by the user. Empty and duplicate tags are
+/// dropped.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split(',').map(|t| t.trim()) {
+        if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_owned());
+        }
+    }
+    tags
+}
+
+/// Pauses between attempts to take a backup while the game still holds a savegame file open.
+const RETRY_DELAYS_MILLIS: [u64; 5] = [250, 500, 1_000, 2_000, 4_000];
+
+/// Prefix of the hidden folders and archives backups are written to before they are complete.
+const STAGING_PREFIX: &str = ".staging_";
+
+/// Removes staging folders and archives of backups that were never completed, e.g. because the
+/// application crashed or the disk ran full. Returns the names of the removed entries.
+///
+/// Only call this when no backup is being taken for `dst_path`.
+pub fn clean_up_staging(dst_path: &String) -> Vec<String> {
+    let mut removed: Vec<String> = vec![];
+    if let Ok(entries) = std::fs::read_dir(dst_path) {
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            let name = String::from(entry_path.file_name().unwrap_or_default().to_str().unwrap_or_default());
+            if !name.starts_with(STAGING_PREFIX) {
+                continue;
+            }
+            let result = if entry_path.is_dir() { std::fs::remove_dir_all(&entry_path) } else { std::fs::remove_file(&entry_path) };
+            if result.is_ok() {
+                removed.push(name);
+            }
+        }
+    }
+    removed
+}
+
+/// Everything needed to write one backup.
+struct BackupRequest<'a> {
+    src_path: &'a String,
+    dst_path: &'a String,
+    backup_name: &'a String,
+    screenshot: Option<&'a PathBuf>,
+    storage: &'a StorageMode,
+    kind: BackupKind,
+    filter: &'a FileFilter,
+}
+
+/// Writes the backup into a hidden staging folder and only moves it into place once every
+/// file and `meta.json` is complete, so an interrupted backup never looks like a real one.
+fn take_backup(request: &BackupRequest) -> Result<(), anyhow::Error> {
+    let BackupRequest { dst_path, backup_name, storage, .. } = *request;
+    if backup_exists(dst_path, backup_name) {
+        return Err(anyhow::anyhow!("A backup called {} already exists", backup_name));
+    }
+
+    if let Some(extension) = archive::extension(storage) {
+        let final_pathbuf = PathBuf::from(dst_path).join(format!("{}.{}", backup_name, extension));
+        let staging_pathbuf = PathBuf::from(dst_path).join(format!("{}{}.{}", STAGING_PREFIX, backup_name, extension));
+        if staging_pathbuf.exists() {
+            std::fs::remove_file(&staging_pathbuf)?;
+        }
+
+        return match write_archive_backup(request, &staging_pathbuf).and_then(|_| Ok(std::fs::rename(&staging_pathbuf, &final_pathbuf)?)) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                std::fs::remove_file(&staging_pathbuf).unwrap_or_default();
+                Err(err)
+            }
+        };
+    }
+
+    let final_pathbuf = PathBuf::from(dst_path).join(backup_name);
+
+    let staging_pathbuf = PathBuf::from(dst_path).join(format!("{}{}", STAGING_PREFIX, backup_name));
+    if staging_pathbuf.exists() {
+        std::fs::remove_dir_all(&staging_pathbuf)?;
+    }
+    std::fs::create_dir(&staging_pathbuf)?;
+
+    match write_backup(request, &staging_pathbuf).and_then(|_| Ok(std::fs::rename(&staging_pathbuf, &final_pathbuf)?)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            std::fs::remove_dir_all(&staging_pathbuf).unwrap_or_default();
+            Err(err)
+        }
+    }
+}
+
+fn write_backup(request: &BackupRequest, dst_pathbuf: &Path) -> Result<(), anyhow::Error> {
+    let BackupRequest { src_path, dst_path, backup_name, screenshot, storage, kind, filter } = *request;
+    let src_pathbuf = PathBuf::from(src_path);
+
+    let file_list = list_source_files(&src_pathbuf, filter)?;
+    let checksum_list = hashcache::hash_files(&file_list)?;
+
+    let mut meta_checksums: Vec<(String, String)> = vec![];
+    for (i, (relative_path, file)) in file_list.iter().enumerate() {
+        let checksum = checksum_list[i].1.clone();
+
+        match storage {
+            StorageMode::Folder => {
+                let new_path = dst_pathbuf.join(relative_path);
+                if let Some(parent) = new_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(file, &new_path)?;
+            },
+            StorageMode::Deduplicated => {
+                storage::store_file(dst_path, file, &checksum)?;
+            },
+            StorageMode::Zip | StorageMode::TarZst => {
+                return Err(anyhow::anyhow!("{} are written by write_archive_backup", storage));
+            },
+        }
+
+        meta_checksums.push((relative_path.clone(), checksum));
+    }
+
+    if let Some(screenshot_path) = screenshot {
+        if screenshot_path.exists() && screenshot_path.is_file() {
+            std::fs::copy(screenshot_path, dst_pathbuf.join("screenshot.jpg")).unwrap_or_default();
+        }
+    }
+
+    let meta_file = File::create(dst_pathbuf.join("meta.json"))?;
+    let now = chrono::Local::now();
+    serde_json::to_writer_pretty(&meta_file, &SavegameMeta { name: backup_name.clone(), date: now.timestamp_millis(), kind, checksums: meta_checksums, storage: *storage, ..Default::default() })?;
+    meta_file.sync_all()?;
+
+    Ok(())
+}
+
+/// Writes the source files, the screenshot and `meta.json` into a single archive at `archive_path`.
+fn write_archive_backup(request: &BackupRequest, archive_path: &PathBuf) -> Result<(), anyhow::Error> {
+    let BackupRequest { src_path, backup_name, screenshot, storage, kind, filter, .. } = *request;
+    let mut file_list = list_source_files(&PathBuf::from(src_path), filter)?;
+    let meta_checksums = hashcache::hash_files(&file_list)?;
+
+    let screenshot = screenshot.filter(|path| path.exists() && path.is_file());
+    if let Some(screenshot_path) = screenshot {
+        file_list.push((String::from("screenshot.jpg"), screenshot_path.clone()));
+    }
+
+    let now = chrono::Local::now();
+    let meta = serde_json::to_vec_pretty(&SavegameMeta { name: backup_name.clone(), date: now.timestamp_millis(), kind, checksums: meta_checksums, storage: *storage, ..Default::default() })?;
+    archive::write_archive(archive_path, storage, &file_list, &meta)?;
+
+    Ok(())
+}
+
+/// A screenshot file. Temporary ones, like screenshots extracted from an archive, are removed
+/// when this is dropped.
+pub struct ScreenshotFile {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl ScreenshotFile {
+    fn temporary(path: PathBuf) -> Self {
+        Self { path, temporary: true }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps a temporary file, e.g. because another program opens it after this returned.
+    pub fn keep(mut self) -> PathBuf {
+        self.temporary = false;
+        self.path.clone()
+    }
+}
+
+impl Drop for ScreenshotFile {
+    fn drop(&mut self) {
+        if self.temporary && self.path.is_file() {
+            std::fs::remove_file(&self.path).unwrap_or_default();
+        }
+    }
+}
+
+/// Screenshot of the backup called `backup_name`, if it has one. Screenshots of archived
+/// backups are extracted into the temporary folder first and removed again once the returned
+/// [`ScreenshotFile`] is dropped.
+pub fn screenshot_path(dst_path: &String, backup_name: &String) -> Option<ScreenshotFile> {
+    let screenshot_pathbuf = PathBuf::from(dst_path).join(backup_name).join("screenshot.jpg");
+    if screenshot_pathbuf.exists() && screenshot_pathbuf.is_file() {
+        return Some(ScreenshotFile { path: screenshot_pathbuf, temporary: false });
+    }
+
+    let archive_path = archive::find_archive(dst_path, backup_name)?;
+    let content = archive::read_file(&archive_path, "screenshot.jpg").ok()??;
+    let screenshot = ScreenshotFile::temporary(std::env::temp_dir().join(format!("savegame_manager_{}.jpg", backup_name)));
+    std::fs::write(screenshot.path(), content).ok()?;
+    Some(screenshot)
+}
+
+/// Content of `file` as stored in `backup`. Returns `None` if the backup does not contain the file.
+pub fn read_backup_file(dst_path: &String, backup: &SavegameMeta, file: &String) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let checksum = match backup.checksums.iter().find(|(name, _)| name == file) {
+        Some((_, checksum)) => checksum,
+        None => return Ok(None),
+    };
+
+    match archive::find_archive(dst_path, &backup.name) {
+        Some(archive_path) => archive::read_file(&archive_path, file),
+        None => Ok(Some(std::fs::read(storage::stored_file_path(dst_path, backup, file, checksum))?)),
+    }
+}
+
+/// Whether a backup called `backup_name` exists, either as folder or as archive.
+pub fn backup_exists(dst_path: &String, backup_name: &String) -> bool {
+    PathBuf::from(dst_path).join(backup_name).exists() || archive::find_archive(dst_path, backup_name).is_some()
+}
+
+/// Copies the source folder into a new backup called `backup_name` and returns its name.
+/// If a `screenshot` is given and exists, it is moved into the backup. Only files matching
+/// `filter` are backed up. While the game holds a file open the backup is retried a few times
+/// with growing pauses.
+pub fn create_backup(src_path: &String, dst_path: &String, backup_name: &String, screenshot: Option<&PathBuf>, storage: &StorageMode, kind: BackupKind, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    if src_path.is_empty() || dst_path.is_empty() {
+        return Err(anyhow::anyhow!("Source or destination path is empty"));
+    }
+
+    let result = stability::retry_while_in_use(&RETRY_DELAYS_MILLIS, is_sharing_violation, || {
+        take_backup(&BackupRequest { src_path, dst_path, backup_name, screenshot, storage, kind, filter })
+    });
+
+    match result {
+        Ok(_) => {
+            // Only now, a retried attempt still needs the screenshot
+            if let Some(screenshot_path) = screenshot.filter(|path| path.is_file()) {
+                std::fs::remove_file(screenshot_path).unwrap_or_default();
+            }
+            Ok(backup_name.clone())
+        },
+        Err(err) => {
+            println!("Error creating backup from {} to {}: {:?}", src_path, dst_path, err);
+            Err(anyhow::anyhow!("Error creating backup: {}", err))
+        }
+    }
+}
+
+fn sorted_backups(dst_path: &String) -> Vec<SavegameMeta> {
+    let mut backup_list = look_for_backups(dst_path).unwrap_or_default();
+    backup_list.sort_by_key(|backup| std::cmp::Reverse(backup.date));
+    backup_list
+}
+
+/// Size of `backup` on disk in bytes. Deduplicated backups count the full size of every
+/// stored file they use, even if other backups share it.
+pub fn backup_size(dst_path: &String, backup: &SavegameMeta) -> u64 {
+    if let Some(archive_path) = archive::find_archive(dst_path, &backup.name) {
+        return archive_path.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+
+    let file_size = |path: &PathBuf| path.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut size: u64 = list_files_recursive(&PathBuf::from(dst_path).join(&backup.name)).unwrap_or_default().iter().map(|(_, path)| file_size(path)).sum();
+    if backup.storage == StorageMode::Deduplicated {
+        size += backup.checksums.iter().map(|(_, checksum)| file_size(&storage::object_path(dst_path, checksum))).sum::<u64>();
+    }
+    size
+}
+
+/// Deletes all temporary saves, all but the newest `max_autosaves` autosaves and every backup
+/// the retention `policy` does not cover. Returns what was deleted and why.
+pub fn prune_backups(dst_path: &String, max_autosaves: &u16, policy: &retention::RetentionPolicy) -> retention::PruneReport {
+    let now = chrono::Local::now().timestamp_millis();
+    let mut report = retention::PruneReport::default();
+    for pruned in retention::plan_prune(&sorted_backups(dst_path), *max_autosaves, policy, now, |backup| backup_size(dst_path, backup)) {
+        match delete_backup(dst_path, &pruned.name) {
+            Ok(_) => {
+                println!("Pruned backup {}: {}", pruned.name, pruned.reason);
+                report.removed.push(pruned);
+            },
+            Err(err) => println!("Error pruning backup {}: {:?}", pruned.name, err),
+        }
+    }
+    report
+}
+
+pub fn create_autosave(src_path: &String, dst_path: &String, screenshot: Option<&PathBuf>, storage: &StorageMode, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    let now = chrono::Local::now();
+    let backup_name = now.format("auto_%Y-%m-%d_%H-%M-%S").to_string();
+    create_backup(src_path, dst_path, &backup_name, screenshot, storage, BackupKind::Auto, filter)
+}
+
+pub fn create_tempsave(src_path: &String, dst_path: &String, screenshot: Option<&PathBuf>, storage: &StorageMode, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    for backup in &sorted_backups(dst_path) {
+        if backup.is_temp() && !backup.pinned {
+            let _ = delete_backup(dst_path, &backup.name);
+        }
+    }
+
+    let now = chrono::Local::now();
+    let backup_name = now.format("temp_%Y-%m-%d_%H-%M-%S").to_string();
+
+    create_backup(src_path, dst_path, &backup_name, screenshot, storage, BackupKind::Temp, filter)
+}
+
+pub fn create_savetokeep(src_path: &String, dst_path: &String, screenshot: Option<&PathBuf>, storage: &StorageMode, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    for backup in &sorted_backups(dst_path) {
+        if backup.is_temp() && !backup.pinned {
+            let _ = delete_backup(dst_path, &backup.name);
+        }
+    }
+
+    let now = chrono::Local::now();
+    let backup_name = now.format("%Y-%m-%d_%H-%M-%S").to_string();
+    create_backup(src_path, dst_path, &backup_name, screenshot, storage, BackupKind::Keep, filter)
+}
+
+/// Backup requested explicitly by the user. Temporary saves are left alone.
+pub fn create_manual_save(src_path: &String, dst_path: &String, screenshot: Option<&PathBuf>, storage: &StorageMode, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    let now = chrono::Local::now();
+    let backup_name = now.format("manual_%Y-%m-%d_%H-%M-%S").to_string();
+    create_backup(src_path, dst_path, &backup_name, screenshot, storage, BackupKind::Manual, filter)
+}
+
+/// The kind of backup that should be taken for the current state of the source folder.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BackupAction {
+    /// The live files have nothing in common with the latest backup. It is kept forever.
+    Keep,
+    /// Some files changed shortly after the latest backup. It is replaced by the next one.
+    Temp,
+    /// Some files changed and the autosave interval has passed. It is rotated out eventually.
+    Auto,
+    /// Requested by the user regardless of what changed. Never chosen by [`classify_backup`].
+    Manual,
+    /// Nothing changed since the latest backup.
+    Skip,
+}
+
+/// Decides which kind of backup to take by comparing the live hashes against the latest
+/// backup that is not a temporary one. Without manual save detection every backup is kept.
+pub fn classify_backup(profile: &profile::SavegameManagerProfile, live_hashes: &Vec<(String, String)>, latest_non_temp: Option<&SavegameMeta>) -> BackupAction {
+    if !profile.manual_save_detection {
+        return BackupAction::Keep;
+    }
+
+    match latest_non_temp {
+        Some(savegame) => {
+            match hash_list_cmp(live_hashes, &savegame.checksums) {
+                BackupComparison::CompleteDiff => BackupAction::Keep,
+                BackupComparison::PartialDiff => {
+                    if chrono::Local::now().timestamp_millis() - savegame.date > profile.auto_saves_interval_millis() {
+                        BackupAction::Auto
+                    } else {
+                        BackupAction::Temp
+                    }
+                },
+                BackupComparison::NoDiff => BackupAction::Skip,
+            }
+        },
+        None => BackupAction::Keep,
+    }
+}
+
+/// Returns the newest backup in `backups` that is not a temporary save or a pre-restore snapshot.
+pub fn latest_non_temp(backups: &[SavegameMeta]) -> Option<SavegameMeta> {
+    backups.iter().filter(|b| !b.is_temp() && !b.is_prerestore()).max_by_key(|b| b.date).cloned()
+}
+
+/// Result of [`run_backup_action`].
+#[derive(Clone, Debug, Default)]
+pub struct BackupOutcome {
+    /// Name of the new backup, `None` if the action was [`BackupAction::Skip`].
+    pub name: Option<String>,
+    /// Backups that were pruned after the new backup was taken.
+    pub pruned: retention::PruneReport,
+}
+
+/// Takes a backup of the given kind for `profile`. Kept, manual and autosaves are followed by a
+/// prune according to the profile's autosave maximum and retention policy.
+pub fn run_backup_action(action: BackupAction, profile: &profile::SavegameManagerProfile, screenshot: Option<&PathBuf>) -> Result<BackupOutcome, anyhow::Error> {
+    // A screenshot that was not moved into a backup, because it was skipped or failed, must
+    // neither be left behind nor be picked up by a later backup
+    let _pending_screenshot = screenshot.map(|path| ScreenshotFile::temporary(path.clone()));
+    let filter = profile.file_filter();
+    let name = match action {
+        BackupAction::Keep => create_savetokeep(&profile.src_path, &profile.dst_path, screenshot, &profile.storage_mode, &filter)?,
+        BackupAction::Temp => create_tempsave(&profile.src_path, &profile.dst_path, screenshot, &profile.storage_mode, &filter)?,
+        BackupAction::Auto => create_autosave(&profile.src_path, &profile.dst_path, screenshot, &profile.storage_mode, &filter)?,
+        BackupAction::Manual => create_manual_save(&profile.src_path, &profile.dst_path, screenshot, &profile.storage_mode, &filter)?,
+        BackupAction::Skip => return Ok(BackupOutcome::default()),
+    };
+
+    let pruned = match action {
+        BackupAction::Keep | BackupAction::Auto | BackupAction::Manual => prune_backups(&profile.dst_path, &profile.auto_saves_max, &profile.retention),
+        _ => retention::PruneReport::default(),
+    };
+
+    Ok(BackupOutcome { name: Some(name), pruned })
+}
+
+pub fn get_meta_for_backup(dst_path: &String, backup_name: &String) -> Result<SavegameMeta, anyhow::Error> {
+    let bak_pathbuf = PathBuf::from(dst_path).join(backup_name);
+    
+    if bak_pathbuf.exists() && bak_pathbuf.is_dir() {
+        let meta_file_path = bak_pathbuf.join("meta.json");
+        if meta_file_path.exists() && meta_file_path.is_file() {
+            let mut meta = schema::parse_meta(backup_name, &std::fs::read(meta_file_path)?)?;
+            meta.name = backup_name.clone();
+            Ok(meta)
+        } else {
+            Err(anyhow::anyhow!("Backup meta file does not exist"))
+        }
+    } else if let Some(archive_path) = archive::find_archive(dst_path, backup_name) {
+        match archive::read_file(&archive_path, "meta.json")? {
+            Some(content) => {
+                let mut meta = schema::parse_meta(backup_name, &content)?;
+                meta.name = backup_name.clone();
+                Ok(meta)
+            },
+            None => Err(anyhow::anyhow!("Backup archive does not contain a meta file")),
+        }
+    } else {
+        Err(anyhow::anyhow!("Backup directory does not exist or is a file"))
+    }
+}
+
+/// Writes `backup` back into its `meta.json`, e.g. after it was pinned. The old file is only
+/// replaced once the new one is complete.
+pub fn write_meta(dst_path: &String, backup: &SavegameMeta) -> Result<(), anyhow::Error> {
+    let content = serde_json::to_vec_pretty(backup)?;
+
+    let bak_pathbuf = PathBuf::from(dst_path).join(&backup.name);
+    if bak_pathbuf.is_dir() {
+        let temp_path = bak_pathbuf.join("meta.json.tmp");
+        let mut meta_file = File::create(&temp_path)?;
+        meta_file.write_all(&content)?;
+        meta_file.sync_all()?;
+        std::fs::rename(&temp_path, bak_pathbuf.join("meta.json"))?;
+        Ok(())
+    } else if let Some(archive_path) = archive::find_archive(dst_path, &backup.name) {
+        let file_name = archive_path.file_name().unwrap_or_default().to_str().unwrap_or_default();
+        let temp_path = PathBuf::from(dst_path).join(format!("{}{}", STAGING_PREFIX, file_name));
+        archive::replace_meta(&archive_path, &temp_path, &content)
+    } else {
+        Err(anyhow::anyhow!("Backup directory does not exist or is a file"))
+    }
+}
+
+/// Replaces notes and tags of the backup called `backup_name` and returns its updated meta data.
+pub fn set_annotations(dst_path: &String, backup_name: &String, notes: &String, tags: &Vec<String>) -> Result<SavegameMeta, anyhow::Error> {
+    let mut meta = get_meta_for_backup(dst_path, backup_name)?;
+    if meta.notes != *notes || meta.tags != *tags {
+        meta.notes = notes.clone();
+        meta.tags = tags.clone();
+        write_meta(dst_path, &meta)?;
+    }
+    Ok(meta)
+}
+
+/// Rewrites the `meta.json` of `backup` with the current schema version if it was read from
+/// an older one. Returns whether it was rewritten.
+pub fn upgrade_meta(dst_path: &String, backup: &mut SavegameMeta) -> Result<bool, anyhow::Error> {
+    if !backup.needs_upgrade() {
+        return Ok(false);
+    }
+    write_meta(dst_path, backup)?;
+    backup.stored_schema_version = backup.schema_version;
+    Ok(true)
+}
+
+/// Rewrites every `meta.json` in `dst_path` that was written with an older schema version.
+/// Returns the names of the upgraded backups.
+pub fn upgrade_meta_files(dst_path: &String) -> Result<Vec<String>, anyhow::Error> {
+    let mut upgraded: Vec<String> = vec![];
+    for mut backup in look_for_backups(dst_path)? {
+        if upgrade_meta(dst_path, &mut backup)? {
+            upgraded.push(backup.name);
+        }
+    }
+    Ok(upgraded)
+}
+
+/// Pins or unpins the backup called `backup_name` and returns its updated meta data.
+pub fn set_pinned(dst_path: &String, backup_name: &String, pinned: bool) -> Result<SavegameMeta, anyhow::Error> {
+    let mut meta = get_meta_for_backup(dst_path, backup_name)?;
+    if meta.pinned != pinned {
+        meta.pinned = pinned;
+        write_meta(dst_path, &meta)?;
+    }
+    Ok(meta)
+}
+
+/// Names of every folder and archive in `dst_path` that could be a backup, whether its
+/// `meta.json` can be read or not.
+pub fn list_backup_names(dst_path: &String) -> Result<Vec<String>, anyhow::Error> {
+    let mut names: Vec<String> = vec![];
+
+    let dst_pathbuf = PathBuf::from(dst_path);
+    if !dst_pathbuf.exists() || !dst_pathbuf.is_dir() {
+        return Err(anyhow::anyhow!("Destination path does not exist or is not a directory"));
+    }
+
+    let files = std::fs::read_dir(dst_pathbuf)?;
+    for file in files {
+        let file_path = file?.path();
+        let file_name = String::from(file_path.file_name().unwrap_or_default().to_str().unwrap_or_default());
+        if file_name.starts_with(".") {
+            // Staging folders and other internal files
+            continue;
+        }
+
+        let backup_name = if file_path.is_dir() {
+            file_name
+        } else {
+            match archive::backup_name_from_archive(&file_name) {
+                Some(name) => name,
+                None => continue,
+            }
+        };
+
+        names.push(backup_name);
+    }
+
+    Ok(names)
+}
+
+/// Meta data of every backup in `dst_path`. Backups without a readable `meta.json` are left
+/// out, see [`repair::find_broken_backups`].
+pub fn look_for_backups(dst_path: &String) -> Result<Vec<SavegameMeta>, anyhow::Error> {
+    let mut backups: Vec<SavegameMeta> = vec![];
+    for backup_name in list_backup_names(dst_path)? {
+        match get_meta_for_backup(dst_path, &backup_name) {
+            Ok(meta) => backups.push(meta),
+            Err(err) => println!("Error reading backup meta for {}: {:?}", backup_name, err),
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Walks `root` recursively and returns every file as a pair of its path relative to `root`
+/// (always using `/` as separator, so it can be stored in `meta.json`) and its absolute path.
+pub fn list_files_recursive(root: &Path) -> Result<Vec<(String, PathBuf)>, std::io::Error> {
+    let mut files: Vec<(String, PathBuf)> = vec![];
+    let mut dirs: Vec<(String, PathBuf)> = vec![(String::new(), root.to_path_buf())];
+
+    while let Some((prefix, dir)) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry_path = entry?.path();
+            let file_name = String::from(entry_path.file_name().unwrap_or_default().to_str().unwrap_or_default());
+            let relative_path = if prefix.is_empty() { file_name } else { format!("{}/{}", prefix, file_name) };
+
+            if entry_path.is_dir() {
+                dirs.push((relative_path, entry_path));
+            } else if entry_path.is_file() {
+                files.push((relative_path, entry_path));
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Turns a relative path as stored in `meta.json` back into a path below `root`.
+/// Older backups only contain plain file names, which resolve the same way.
+pub fn relative_to_path(root: &Path, relative_path: &str) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for part in relative_path.split(['/', '\\']) {
+        if !part.is_empty() && part != "." && part != ".." {
+            path.push(part);
+        }
+    }
+    path
+}
+
+/// Removes every empty directory below `root` (but not `root` itself).
+fn remove_empty_dirs(root: &PathBuf) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(root)? {
+        let entry_path = entry?.path();
+        if entry_path.is_dir() {
+            remove_empty_dirs(&entry_path)?;
+            if std::fs::read_dir(&entry_path)?.next().is_none() {
+                std::fs::remove_dir(&entry_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Files of the source folder `root` that match `filter`. See [`list_files_recursive`].
+pub fn list_source_files(root: &Path, filter: &FileFilter) -> Result<Vec<(String, PathBuf)>, std::io::Error> {
+    let mut files = list_files_recursive(root)?;
+    if !filter.is_empty() {
+        files.retain(|(relative_path, _)| filter.matches(relative_path));
+    }
+    Ok(files)
+}
+
+/// Checksums of the files in `src_path` matching `filter`. Unchanged files are taken from
+/// `cache`. Fails if a file can't be read, e.g. because the game holds it open or deleted it
+/// while the folder was scanned.
+pub fn create_hash_list(src_path: &String, filter: &FileFilter, cache: &mut HashCache) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let pathbuf = PathBuf::from(src_path);
+    if pathbuf.exists() && pathbuf.is_dir() {
+        cache.hash_files(&pathbuf, &list_source_files(&pathbuf, filter)?)
+    } else {
+        Ok(vec![])
+    }
+}
+
+pub enum BackupComparison {
+    CompleteDiff,
+    PartialDiff,
+    NoDiff,
+}
+
+impl std::ops::Not for BackupComparison {
+    type Output = bool;
+
+    fn not(self) -> Self::Output {
+        match self {
+            BackupComparison::CompleteDiff => true,
+            BackupComparison::PartialDiff => true,
+            BackupComparison::NoDiff => false,
+        }
+    }
+}
+
+pub fn hash_list_cmp(hashes: &Vec<(String, String)>, cmp_with: &Vec<(String, String)>) -> BackupComparison {
+    let mut some_matches = false;
+    let mut all_matches = true;
+
+    for hash in hashes {
+        let mut file_found = false;
+        for cmp_hash in cmp_with {
+            if hash.0 == cmp_hash.0 {
+                file_found = true;
+                if hash.1 != cmp_hash.1 {
+                    all_matches = false;
+                } else {
+                    some_matches = true;
+                }
+                break;
+            }
+        }
+
+        if !file_found {
+            all_matches = false;
+        }
+    }
+
+    if all_matches {
+        BackupComparison::NoDiff
+    } else if some_matches {
+        BackupComparison::PartialDiff
+    } else {
+        BackupComparison::CompleteDiff
+    }
+}
+
+/// Result of comparing the files of a backup folder against the checksums in its `meta.json`.
+#[derive(Clone, Default, Debug, Serialize)]
+pub struct VerifyReport {
+    /// Files listed in `meta.json` that are not in the backup folder
+    pub missing: Vec<String>,
+    /// Files in the backup folder that are not listed in `meta.json`
+    pub extra: Vec<String>,
+    /// Files whose content does not match their checksum
+    pub corrupted: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Whether every file of the backup is present and intact. Extra files do not count, as
+    /// they are never restored.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty()
+    }
+}
+
+impl std::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.missing.is_empty() && self.extra.is_empty() && self.corrupted.is_empty() {
+            return write!(f, "All files are intact");
+        }
+
+        let mut lines: Vec<String> = vec![];
+        lines.extend(self.missing.iter().map(|file| format!("Missing: {}", file)));
+        lines.extend(self.corrupted.iter().map(|file| format!("Corrupted: {}", file)));
+        lines.extend(self.extra.iter().map(|file| format!("Extra: {}", file)));
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Files the backup itself adds next to the savegame files.
+pub fn is_bookkeeping_file(relative_path: &String) -> bool {
+    relative_path == "meta.json" || relative_path == "meta.json.tmp" || relative_path == "screenshot.jpg"
+}
+
+/// Re-hashes every file of `backup` and compares it against the stored checksums.
+/// Archives are extracted into a temporary folder for this.
+pub fn verify_backup(dst_path: &String, backup: &SavegameMeta) -> Result<VerifyReport, anyhow::Error> {
+    if let Some(archive_path) = archive::find_archive(dst_path, &backup.name) {
+        let temp_pathbuf = std::env::temp_dir().join(format!("savegame_manager_verify_{}_{}", std::process::id(), backup.name));
+        if temp_pathbuf.exists() {
+            std::fs::remove_dir_all(&temp_pathbuf)?;
+        }
+        let result = archive::extract(&archive_path, &temp_pathbuf).and_then(|_| verify_files(&temp_pathbuf, backup, |file, _| relative_to_path(&temp_pathbuf, file)));
+        std::fs::remove_dir_all(&temp_pathbuf).unwrap_or_default();
+        return result;
+    }
+
+    let bak_pathbuf = PathBuf::from(dst_path).join(&backup.name);
+    if !bak_pathbuf.is_dir() {
+        return Err(anyhow::anyhow!("Backup directory does not exist or is a file"));
+    }
+
+    verify_files(&bak_pathbuf, backup, |file, checksum| storage::stored_file_path(dst_path, backup, file, checksum))
+}
+
+/// Compares the files of `backup` found through `file_path` against their checksums and lists
+/// unexpected files in `bak_pathbuf`.
+fn verify_files(bak_pathbuf: &Path, backup: &SavegameMeta, file_path: impl Fn(&String, &String) -> PathBuf) -> Result<VerifyReport, anyhow::Error> {
+    let mut report = VerifyReport::default();
+    for (file, checksum) in &backup.checksums {
+        let file_path = file_path(file, checksum);
+        if !file_path.is_file() {
+            report.missing.push(file.clone());
+        } else {
+            match fhc::file_blake3(&file_path) {
+                Ok(hash) if hash == *checksum => {},
+                _ => report.corrupted.push(file.clone()),
+            }
+        }
+    }
+
+    for (relative_path, _) in list_files_recursive(bak_pathbuf)? {
+        if !is_bookkeeping_file(&relative_path) && !backup.checksums.iter().any(|(file, _)| relative_to_path(bak_pathbuf, file) == relative_to_path(bak_pathbuf, &relative_path)) {
+            report.extra.push(relative_path);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Replaces the live files matching `filter` with the files of `backup`. Live files the filter
+/// excludes are left alone and excluded files of the backup are not restored. Checksums of
+/// live files that did not change are taken from `cache`.
+pub fn load_backup(src_path: &String, dst_path: &String, backup: &SavegameMeta, filter: &FileFilter, cache: &mut HashCache) -> Result<(), anyhow::Error> {
+    let report = verify_backup(dst_path, backup)?;
+    if !report.is_ok() {
+        return Err(anyhow::anyhow!("Backup {} is damaged and was not restored.\n{}", backup.name, report));
+    }
+
+    let mut selection = backup.clone();
+    selection.checksums.retain(|(file, _)| filter.matches(file));
+
+    let hash_list = create_hash_list(src_path, filter, cache)?;
+    if !hash_list_cmp(&selection.checksums, &hash_list) || hash_list.len() != selection.checksums.len() {
+        if !backup.is_prerestore() && !hash_list.is_empty() {
+            create_prerestore(src_path, dst_path, filter)?;
+        }
+
+        restore_files(&PathBuf::from(src_path), dst_path, &selection, &hash_list)?;
+    }
+
+    Ok(())
+}
+
+/// Restores only `files` of `backup` and leaves every other live file alone. Only the selected
+/// files have to be intact. Like [`load_backup`], a snapshot of the live files is taken first.
+pub fn load_backup_files(src_path: &String, dst_path: &String, backup: &SavegameMeta, files: &[String], cache: &mut HashCache) -> Result<(), anyhow::Error> {
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("No files were selected"));
+    }
+    if let Some(file) = files.iter().find(|file| !backup.checksums.iter().any(|(f, _)| f == *file)) {
+        return Err(anyhow::anyhow!("Backup {} does not contain {}", backup.name, file));
+    }
+
+    let report = verify_backup(dst_path, backup)?;
+    let damaged: Vec<&String> = report.missing.iter().chain(report.corrupted.iter()).filter(|file| files.contains(file)).collect();
+    if !damaged.is_empty() {
+        return Err(anyhow::anyhow!("Files of backup {} are damaged and were not restored: {}", backup.name, damaged.iter().map(|f| f.as_str()).collect::<Vec<&str>>().join(", ")));
+    }
+
+    let mut selection = backup.clone();
+    selection.checksums.retain(|(file, _)| files.contains(file));
+
+    // Explicitly selected files are restored even if the profile excludes them
+    let hash_list = create_hash_list(src_path, &FileFilter::default(), cache)?;
+    let live_selection: Vec<(String, String)> = hash_list.iter().filter(|(file, _)| files.contains(file)).cloned().collect();
+    if !hash_list_cmp(&selection.checksums, &live_selection) || live_selection.len() != selection.checksums.len() {
+        if !backup.is_prerestore() && !hash_list.is_empty() {
+            create_prerestore(src_path, dst_path, &FileFilter::default())?;
+        }
+
+        restore_files(&PathBuf::from(src_path), dst_path, &selection, &live_selection)?;
+    }
+
+    Ok(())
+}
+
+/// Returns a hidden folder next to `src_pathbuf`, which is on the same volume, so files
+/// can be moved between them without copying.
+fn sibling_folder(src_pathbuf: &Path, suffix: &str) -> Result<PathBuf, anyhow::Error> {
+    match (src_pathbuf.parent(), src_pathbuf.file_name()) {
+        (Some(parent), Some(name)) => Ok(parent.join(format!(".{}.{}", name.to_str().unwrap_or_default(), suffix))),
+        _ => Err(anyhow::anyhow!("Savegame folder has no parent folder")),
+    }
+}
+
+fn move_file(from: &PathBuf, to: &PathBuf) -> Result<(), std::io::Error> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(from, to)
+}
+
+/// Replaces the `live_files` in `src_pathbuf` with the files of `backup`.
+///
+/// The backup files are copied into a staging folder and verified first. Then the live files
+/// are moved aside and the staged files are moved in. If anything fails on the way, the
+/// original files are put back, so the savegame folder is never left half restored.
+fn restore_files(src_pathbuf: &PathBuf, dst_path: &String, backup: &SavegameMeta, live_files: &Vec<(String, String)>) -> Result<(), anyhow::Error> {
+    let backup_files = &backup.checksums;
+    let staging_pathbuf = sibling_folder(src_pathbuf, "restore")?;
+    let rollback_pathbuf = sibling_folder(src_pathbuf, "rollback")?;
+
+    if rollback_pathbuf.exists() {
+        return Err(anyhow::anyhow!("An earlier restore was interrupted. The files it replaced are still in {}", rollback_pathbuf.display()));
+    }
+    if staging_pathbuf.exists() {
+        std::fs::remove_dir_all(&staging_pathbuf)?;
+    }
+
+    // Copy and verify everything before the live files are touched
+    let staged = (|| -> Result<(), anyhow::Error> {
+        let archive_path = archive::find_archive(dst_path, &backup.name);
+        if let Some(archive_path) = &archive_path {
+            archive::extract(archive_path, &staging_pathbuf)?;
+        }
+        for (file, checksum) in backup_files {
+            let staged_path = relative_to_path(&staging_pathbuf, file);
+            if archive_path.is_none() {
+                if let Some(parent) = staged_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(storage::stored_file_path(dst_path, backup, file, checksum), &staged_path)?;
+            }
+            if fhc::file_blake3(&staged_path)? != *checksum {
+                return Err(anyhow::anyhow!("Copy of {} does not match its checksum", file));
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = staged {
+        std::fs::remove_dir_all(&staging_pathbuf).unwrap_or_default();
+        return Err(err);
+    }
+
+    let mut moved_out: Vec<&String> = vec![];
+    let mut moved_in: Vec<&String> = vec![];
+    let swapped = (|| -> Result<(), anyhow::Error> {
+        for (file, _) in live_files {
+            move_file(&relative_to_path(src_pathbuf, file), &relative_to_path(&rollback_pathbuf, file))?;
+            moved_out.push(file);
+        }
+        for (file, _) in backup_files {
+            move_file(&relative_to_path(&staging_pathbuf, file), &relative_to_path(src_pathbuf, file))?;
+            moved_in.push(file);
+        }
+        Ok(())
+    })();
+
+    match swapped {
+        Ok(_) => {
+            std::fs::remove_dir_all(&rollback_pathbuf).unwrap_or_default();
+            std::fs::remove_dir_all(&staging_pathbuf).unwrap_or_default();
+            remove_empty_dirs(src_pathbuf)?;
+            Ok(())
+        },
+        Err(err) => {
+            println!("Error restoring files, rolling back: {:?}", err);
+            let mut rolled_back = true;
+            for file in moved_in {
+                rolled_back &= std::fs::remove_file(relative_to_path(src_pathbuf, file)).is_ok();
+            }
+            for file in moved_out {
+                rolled_back &= move_file(&relative_to_path(&rollback_pathbuf, file), &relative_to_path(src_pathbuf, file)).is_ok();
+            }
+            std::fs::remove_dir_all(&staging_pathbuf).unwrap_or_default();
+
+            if rolled_back {
+                std::fs::remove_dir_all(&rollback_pathbuf).unwrap_or_default();
+                remove_empty_dirs(src_pathbuf).unwrap_or_default();
+                Err(anyhow::anyhow!("Restore failed, your savegame was left unchanged: {}", err))
+            } else {
+                Err(anyhow::anyhow!("Restore failed and not all files could be put back. The missing files are in {}: {}", rollback_pathbuf.display(), err))
+            }
+        }
+    }
+}
+
+/// Captures the live files matching `filter` as `prerestore_` backup, so a restore can be
+/// undone. Only the newest snapshot is kept.
+pub fn create_prerestore(src_path: &String, dst_path: &String, filter: &FileFilter) -> Result<String, anyhow::Error> {
+    let now = chrono::Local::now();
+    let mut backup_name = now.format("prerestore_%Y-%m-%d_%H-%M-%S").to_string();
+    let mut counter = 1;
+    while backup_exists(dst_path, &backup_name) {
+        counter += 1;
+        backup_name = now.format(&format!("prerestore_%Y-%m-%d_%H-%M-%S_{}", counter)).to_string();
+    }
+
+    // Always a full copy, so undoing a restore does not depend on the store
+    create_backup(src_path, dst_path, &backup_name, None, &StorageMode::Folder, BackupKind::Prerestore, filter)?;
+
+    for backup in &sorted_backups(dst_path) {
+        if backup.is_prerestore() && !backup.pinned && backup.name != backup_name {
+            let _ = delete_backup(dst_path, &backup.name);
+        }
+    }
+
+    Ok(backup_name)
+}
+
+/// Returns the newest pre-restore snapshot, if there is one.
+pub fn latest_prerestore(dst_path: &String) -> Option<SavegameMeta> {
+    sorted_backups(dst_path).into_iter().find(|b| b.is_prerestore())
+}
+
+/// Puts the live files back into the state they were in before the last restore.
+/// Returns the snapshot that was restored.
+pub fn undo_last_restore(src_path: &String, dst_path: &String, filter: &FileFilter, cache: &mut HashCache) -> Result<SavegameMeta, anyhow::Error> {
+    match latest_prerestore(dst_path) {
+        Some(snapshot) => {
+            load_backup(src_path, dst_path, &snapshot, filter, cache)?;
+            Ok(snapshot)
+        },
+        None => Err(anyhow::anyhow!("There is no restore to undo")),
+    }
+}
+
+pub fn deal_with_exit_save(dst_path: &String) {
+    let mut first_temp = true;
+    for backup in &sorted_backups(dst_path) {
+        if backup.is_temp() {
+            if first_temp {
+                let mut exit_save = backup.clone();
+                exit_save.kind = BackupKind::Exit;
+                let _ = write_meta(dst_path, &exit_save);
+                if let Some(suffix) = backup.name.strip_prefix("temp_") {
+                    let _ = rename_backup(dst_path, &backup.name, &format!("exit_{}", suffix));
+                }
+                first_temp = false;
+            } else if !backup.pinned {
+                let _ = delete_backup(dst_path, &backup.name);
+            }
+        }
+    }
+}
+
+/// Renames a backup. Its `meta.json` moves along, so pin, notes and tags are kept. Fails if
+/// another backup is already called `new_name`.
+///
+/// Meta files of older versions are upgraded first. Their kind is only known from the old
+/// name, so without the upgrade the new name would decide how the backup is rotated.
+pub fn rename_backup(dst_path: &String, old_name: &String, new_name: &String) -> Result<(), anyhow::Error> {
+    let old_path = PathBuf::from(dst_path).join(old_name);
+    let new_path = PathBuf::from(dst_path).join(new_name);
+
+    if old_name == new_name {
+        return Ok(());
+    }
+    if backup_exists(dst_path, new_name) {
+        return Err(anyhow::anyhow!("A backup called {} already exists", new_name));
+    }
+
+    if let Ok(mut meta) = get_meta_for_backup(dst_path, old_name) {
+        upgrade_meta(dst_path, &mut meta)?;
+    }
+
+    if old_path.exists() && old_path.is_dir() {
+        std::fs::rename(old_path, new_path)?;
+    } else if let Some(archive_path) = archive::find_archive(dst_path, old_name) {
+        let file_name = String::from(archive_path.file_name().unwrap_or_default().to_str().unwrap_or_default());
+        let extension = file_name.strip_prefix(old_name.as_str()).unwrap_or_default();
+        std::fs::rename(&archive_path, PathBuf::from(dst_path).join(format!("{}{}", new_name, extension)))?;
+    }
+    Ok(())
+}
+
+/// Removes stored contents that are no longer used after a backup was removed.
+fn collect_garbage_after_removal(dst_path: &String) {
+    if let Err(err) = storage::collect_garbage(dst_path) {
+        println!("Error collecting garbage in {}: {:?}", dst_path, err);
+    }
+}
+
+/// Moves a backup to the recycle bin. Stored contents of a deduplicated backup are kept, so
+/// it still works when it is restored from the recycle bin. They are only removed by the
+/// garbage collection after the next permanent delete.
+pub fn recycle_backup(dst_path: &String, backup_name: &String) -> Result<(), trash::Error> {
+    let backup_path = PathBuf::from(dst_path).join(backup_name);
+
+    if backup_path.exists() && backup_path.is_dir() {
+        delete(backup_path)?;
+    } else if let Some(archive_path) = archive::find_archive(dst_path, backup_name) {
+        delete(archive_path)?;
+    }
+    Ok(())
+}
+
+pub fn delete_backup(dst_path: &String, backup_name: &String) -> Result<(), std::io::Error> {
+    let backup_path = PathBuf::from(dst_path).join(backup_name);
+    if backup_path.exists() && backup_path.is_dir() {
+        std::fs::remove_dir_all(backup_path)?;
+        collect_garbage_after_removal(dst_path);
+    } else if let Some(archive_path) = archive::find_archive(dst_path, backup_name) {
+        std::fs::remove_file(archive_path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renaming_keeps_the_kind_of_old_backups() {
+        let dst = test_dir("rename_old_backup");
+        let dst_path = dst.to_string_lossy().to_string();
+        std::fs::create_dir(dst.join("2024-06-10_08-00-00")).unwrap();
+        std::fs::write(dst.join("2024-06-10_08-00-00").join("meta.json"), r#"{"schema_version": 1, "date": 0, "checksums": [], "storage": "Folder", "pinned": false, "notes": "", "tags": []}"#).unwrap();
+
+        rename_backup(&dst_path, &String::from("2024-06-10_08-00-00"), &String::from("auto_renamed")).unwrap();
+
+        let meta = get_meta_for_backup(&dst_path, &String::from("auto_renamed")).unwrap();
+        assert_eq!(meta.kind, BackupKind::Keep);
+        assert!(!meta.needs_upgrade());
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn renaming_fails_if_the_new_name_is_taken() {
+        let dst = test_dir("rename_taken");
+        let dst_path = dst.to_string_lossy().to_string();
+        for name in ["first", "second"] {
+            std::fs::create_dir(dst.join(name)).unwrap();
+            std::fs::write(dst.join(name).join("meta.json"), name).unwrap();
+        }
+
+        assert!(rename_backup(&dst_path, &String::from("first"), &String::from("second")).is_err());
+        assert_eq!(std::fs::read_to_string(dst.join("first").join("meta.json")).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(dst.join("second").join("meta.json")).unwrap(), "second");
+
+        // Keeping the name is not an error, the GUI does so when only notes or tags changed
+        rename_backup(&dst_path, &String::from("first"), &String::from("first")).unwrap();
+        assert!(dst.join("first").is_dir());
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn screenshots_are_moved_into_the_backup() {
+        let src = test_dir("screenshot_src");
+        let dst = test_dir("screenshot_dst");
+        std::fs::write(src.join("slot1.sav"), "save").unwrap();
+        let screenshot = dst.join(".screenshot.jpg");
+        std::fs::write(&screenshot, "jpg").unwrap();
+
+        let dst_path = dst.to_string_lossy().to_string();
+        create_backup(&src.to_string_lossy().to_string(), &dst_path, &String::from("first"), Some(&screenshot), &StorageMode::Folder, BackupKind::Keep, &FileFilter::default()).unwrap();
+
+        assert!(!screenshot.exists());
+        assert_eq!(std::fs::read_to_string(dst.join("first").join("screenshot.jpg")).unwrap(), "jpg");
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn skipped_backups_remove_their_screenshot() {
+        let dst = test_dir("skip_screenshot");
+        let screenshot = dst.join(".screenshot.jpg");
+        std::fs::write(&screenshot, "jpg").unwrap();
+        let profile = profile::SavegameManagerProfile { dst_path: dst.to_string_lossy().to_string(), ..Default::default() };
+
+        let outcome = run_backup_action(BackupAction::Skip, &profile, Some(&screenshot)).unwrap();
+
+        assert!(outcome.name.is_none());
+        assert!(!screenshot.exists());
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use crate::*;
+use backup::SavegameMeta;
+use filter::FileFilter;
+use hashcache::HashCache;
+use storage::StorageMode;
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+use serde::Serialize;
+
+/// What happened to a file between the old and the new side of a comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl std::fmt::Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Added => "Added",
+            Self::Removed => "Removed",
+            Self::Changed => "Changed",
+            Self::Unchanged => "Unchanged",
+        })
+    }
+}
+
+/// One side of a compared file.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FileVersion {
+    pub checksum: String,
+    /// Size in bytes, `None` if the file could not be read.
+    pub size: Option<u64>,
+    /// Modification time as unix milliseconds. Unknown for deduplicated backups, where the
+    /// stored content is shared between backups.
+    pub modified: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileDiff {
+    /// Path relative to the savegame folder as stored in `meta.json`.
+    pub path: String,
+    pub change: FileChange,
+    pub old: Option<FileVersion>,
+    pub new: Option<FileVersion>,
+}
+
+/// File by file comparison of two backups or of a backup and the live files.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BackupDiff {
+    /// Name of the old backup.
+    pub old: String,
+    /// Name of the new backup, `None` for the live files.
+    pub new: Option<String>,
+    /// Every file of both sides, sorted by path.
+    pub files: Vec<FileDiff>,
+}
+
+impl BackupDiff {
+    pub fn count(&self, change: FileChange) -> usize {
+        self.files.iter().filter(|f| f.change == change).count()
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.files.iter().all(|f| f.change == FileChange::Unchanged)
+    }
+
+    /// Short summary like `1 added, 2 changed`.
+    pub fn summary(&self) -> String {
+        if self.is_identical() {
+            return String::from("No differences");
+        }
+
+        let mut parts: Vec<String> = vec![];
+        for change in [FileChange::Added, FileChange::Removed, FileChange::Changed] {
+            let count = self.count(change);
+            if count > 0 {
+                parts.push(format!("{} {}", count, change.to_string().to_lowercase()));
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+impl std::fmt::Display for BackupDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in self.files.iter().filter(|f| f.change != FileChange::Unchanged) {
+            let size = |version: &Option<FileVersion>| version.as_ref().and_then(|v| v.size).map(format_size).unwrap_or(String::from("?"));
+            match file.change {
+                FileChange::Added => writeln!(f, "+ {} ({})", file.path, size(&file.new))?,
+                FileChange::Removed => writeln!(f, "- {} ({})", file.path, size(&file.old))?,
+                _ => writeln!(f, "~ {} ({} -> {})", file.path, size(&file.old), size(&file.new))?,
+            }
+        }
+        write!(f, "{}", self.summary())
+    }
+}
+
+/// Human readable size like `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Compares the files of backup `old` with the files of backup `new`.
+pub fn diff_backups(dst_path: &String, old: &SavegameMeta, new: &SavegameMeta) -> Result<BackupDiff, anyhow::Error> {
+    Ok(BackupDiff {
+        old: old.name.clone(),
+        new: Some(new.name.clone()),
+        files: compare(backup_files(dst_path, old)?, backup_files(dst_path, new)?),
+    })
+}
+
+/// Compares the files of `backup` with the live files in `src_path`. Files that `filter`
+/// excludes are left out on both sides. Checksums of unchanged live files are taken from `cache`.
+pub fn diff_with_live(src_path: &String, dst_path: &String, backup: &SavegameMeta, filter: &FileFilter, cache: &mut HashCache) -> Result<BackupDiff, anyhow::Error> {
+    let mut old = backup_files(dst_path, backup)?;
+    old.retain(|path, _| filter.matches(path));
+    Ok(BackupDiff {
+        old: backup.name.clone(),
+        new: None,
+        files: compare(old, live_files(src_path, filter, cache)?),
+    })
+}
+
+fn compare(old: BTreeMap<String, FileVersion>, mut new: BTreeMap<String, FileVersion>) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = vec![];
+    for (path, old_version) in old {
+        match new.remove(&path) {
+            Some(new_version) => {
+                let change = if old_version.checksum == new_version.checksum { FileChange::Unchanged } else { FileChange::Changed };
+                files.push(FileDiff { path, change, old: Some(old_version), new: Some(new_version) });
+            },
+            None => files.push(FileDiff { path, change: FileChange::Removed, old: Some(old_version), new: None }),
+        }
+    }
+    for (path, new_version) in new {
+        files.push(FileDiff { path, change: FileChange::Added, old: None, new: Some(new_version) });
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn file_version(path: &Path, checksum: &str, with_modified: bool) -> FileVersion {
+    let metadata = path.metadata().ok();
+    FileVersion {
+        checksum: checksum.to_string(),
+        size: metadata.as_ref().map(|m| m.len()),
+        modified: metadata.filter(|_| with_modified).and_then(|m| m.modified().ok()).and_then(millis_from_system_time),
+    }
+}
+
+fn live_files(src_path: &String, filter: &FileFilter, cache: &mut HashCache) -> Result<BTreeMap<String, FileVersion>, anyhow::Error> {
+    let mut files: BTreeMap<String, FileVersion> = BTreeMap::new();
+    let src_pathbuf = PathBuf::from(src_path);
+    let entries = backup::list_source_files(&src_pathbuf, filter)?;
+    for ((relative_path, entry_path), (_, checksum)) in entries.iter().zip(cache.hash_files(&src_pathbuf, &entries)?) {
+        files.insert(relative_path.clone(), file_version(entry_path, &checksum, true));
+    }
+    Ok(files)
+}
+
+fn backup_files(dst_path: &String, backup: &SavegameMeta) -> Result<BTreeMap<String, FileVersion>, anyhow::Error> {
+    let mut files: BTreeMap<String, FileVersion> = BTreeMap::new();
+
+    if let Some(archive_path) = archive::find_archive(dst_path, &backup.name) {
+        let entries = archive::list_files(&archive_path)?;
+        for (file, checksum) in &backup.checksums {
+            let entry = entries.iter().find(|(name, _, _)| name == file);
+            files.insert(file.clone(), FileVersion {
+                checksum: checksum.clone(),
+                size: entry.map(|(_, size, _)| *size),
+                modified: entry.and_then(|(_, _, modified)| *modified),
+            });
+        }
+        return Ok(files);
+    }
+
+    let with_modified = backup.storage != StorageMode::Deduplicated;
+    for (file, checksum) in &backup.checksums {
+        let path = storage::stored_file_path(dst_path, backup, file, checksum);
+        files.insert(file.clone(), file_version(&path, checksum, with_modified));
+    }
+    Ok(files)
+}
@@ -0,0 +1,465 @@
+use crate::*;
+use backup::SavegameMeta;
+use hashcache::HashCache;
+use profile::SavegameManagerProfile;
+use screenshot::ScreenshotState;
+use stability::StabilityDetector;
+use watcher::{SourceWatcher, WatcherHealth};
+
+use std::{path::PathBuf, sync::{Arc, Mutex, RwLock}};
+
+#[derive(Clone)]
+pub enum BackupState {
+    Idle,
+    Busy,
+    Finished,
+}
+
+/// Progress of the [`StabilityDetector`] poll that runs on a worker thread.
+#[derive(Clone)]
+enum PollState {
+    Idle,
+    Busy,
+    /// The files stopped changing, the next tick takes the backup.
+    Stable,
+}
+
+/// Something the frontend should know about, returned by [`BackupEngine::tick`].
+#[derive(Clone, Debug)]
+pub enum EngineEvent {
+    /// A backup with this name was created.
+    BackupCreated(String),
+    /// Creating a backup failed.
+    BackupFailed(String),
+    /// Taking the screenshot for the next backup failed. The backup is taken anyway.
+    ScreenshotFailed(String),
+    /// The watcher fell back to polling, lost the source folder or found it again.
+    WatcherHealthChanged(WatcherHealth),
+}
+
+/// State that is written by the worker threads of an engine.
+struct EngineState {
+    backup_state: RwLock<BackupState>,
+    backup_error: RwLock<String>,
+    backup_name: RwLock<String>,
+    prune_report: RwLock<retention::PruneReport>,
+    screenshot_state: RwLock<ScreenshotState>,
+    screenshot_error: RwLock<String>,
+    stability: Mutex<StabilityDetector>,
+    poll_state: RwLock<PollState>,
+}
+
+impl Default for EngineState {
+    fn default() -> Self {
+        Self {
+            backup_state: RwLock::new(BackupState::Idle),
+            backup_error: RwLock::new(String::new()),
+            backup_name: RwLock::new(String::new()),
+            prune_report: RwLock::new(Default::default()),
+            screenshot_state: RwLock::new(ScreenshotState::Idle),
+            screenshot_error: RwLock::new(String::new()),
+            stability: Mutex::new(StabilityDetector::new()),
+            poll_state: RwLock::new(PollState::Idle),
+        }
+    }
+}
+
+/// Watches and backs up the savegame folder of a single profile.
+///
+/// Each engine owns its watcher, its cached list of backups and the state of the backup
+/// currently in progress, so several engines can run side by side. The engine does not
+/// spawn a loop of its own: call [`BackupEngine::tick`] regularly (the GUI does it every
+/// 500 ms) to take screenshots and backups once the source folder changed. Each tick after the
+/// profile's quiet period starts one poll of the [`StabilityDetector`] on a worker thread.
+pub struct BackupEngine {
+    profile: SavegameManagerProfile,
+    watcher: SourceWatcher,
+    backup_list: Mutex<Vec<SavegameMeta>>,
+    /// Checksums of the live files, kept in the backup folder of the profile.
+    hash_cache: Arc<Mutex<HashCache>>,
+    state: Arc<EngineState>,
+}
+
+impl Default for BackupEngine {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl BackupEngine {
+    pub fn new(profile: SavegameManagerProfile) -> Self {
+        let hash_cache = HashCache::open(&profile.dst_path);
+        Self {
+            profile,
+            watcher: SourceWatcher::new(),
+            backup_list: Mutex::new(vec![]),
+            hash_cache: Arc::new(Mutex::new(hash_cache)),
+            state: Arc::new(EngineState::default()),
+        }
+    }
+
+    pub fn profile(&self) -> &SavegameManagerProfile {
+        &self.profile
+    }
+
+    /// Replaces the profile settings. The watcher is restarted if it was running and one of the
+    /// folders, file patterns or watcher settings changed. Returns `false` if the watcher could
+    /// not be restarted.
+    pub fn set_profile(&mut self, profile: SavegameManagerProfile) -> bool {
+        let paths_changed = profile.src_path != self.profile.src_path || profile.dst_path != self.profile.dst_path;
+        let watcher_changed = profile.file_filter() != self.profile.file_filter()
+            || profile.watcher_backend != self.profile.watcher_backend
+            || profile.poll_interval_millis != self.profile.poll_interval_millis;
+        self.profile = profile;
+
+        if paths_changed {
+            self.backup_list.lock().unwrap().clear();
+            *self.hash_cache.lock().unwrap() = HashCache::open(&self.profile.dst_path);
+        }
+        if paths_changed || watcher_changed {
+            self.start_watcher()
+        } else {
+            true
+        }
+    }
+
+    pub fn start_watcher(&mut self) -> bool {
+        self.watcher.start(&self.profile.src_path, &self.profile.dst_path, self.profile.file_filter(), self.profile.watcher_backend, self.profile.poll_interval())
+    }
+
+    pub fn stop_watcher(&mut self) {
+        self.watcher.stop();
+    }
+
+    pub fn watcher(&self) -> &SourceWatcher {
+        &self.watcher
+    }
+
+    pub fn watcher_health(&self) -> WatcherHealth {
+        self.watcher.health()
+    }
+
+    /// Where the screenshot for the next backup is stored until the backup picks it up.
+    fn screenshot_path(&self) -> PathBuf {
+        PathBuf::from(&self.profile.dst_path).join(".screenshot.jpg")
+    }
+
+    /// Removes leftovers of backups that were interrupted. Call this once at startup, before
+    /// any backup is taken for the profile.
+    pub fn clean_up_staging(&self) -> Vec<String> {
+        backup::clean_up_staging(&self.profile.dst_path)
+    }
+
+    /// Backups as of the last call to [`BackupEngine::refresh_backups`], newest first.
+    pub fn backups(&self) -> Vec<SavegameMeta> {
+        self.backup_list.lock().unwrap().clone()
+    }
+
+    /// Reads all backups of the profile from disk and updates the cached list. Outdated
+    /// `meta.json` files are rewritten if the profile asks for it.
+    pub fn refresh_backups(&self) -> Result<Vec<SavegameMeta>, anyhow::Error> {
+        let mut backups = backup::look_for_backups(&self.profile.dst_path)?;
+        if self.profile.upgrade_meta_files {
+            for backup in &mut backups {
+                if let Err(err) = backup::upgrade_meta(&self.profile.dst_path, backup) {
+                    println!("Error upgrading meta file of {}: {:?}", backup.name, err);
+                }
+            }
+        }
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.date));
+        *self.backup_list.lock().unwrap() = backups.clone();
+        Ok(backups)
+    }
+
+    /// Whether a backup is being taken or waits to be finished up by [`BackupEngine::tick`].
+    pub fn is_busy(&self) -> bool {
+        !matches!(read_rwlock_or(&self.state.backup_state, BackupState::Idle), BackupState::Idle)
+    }
+
+    /// Returns the newest cached backup whose files match the live files exactly. Returns
+    /// `None` if the live files can't be read.
+    pub fn find_live_backup(&self) -> Option<SavegameMeta> {
+        let filter = self.profile.file_filter();
+        let live_hashes = match backup::create_hash_list(&self.profile.src_path, &filter, &mut self.hash_cache.lock().unwrap()) {
+            Ok(live_hashes) => live_hashes,
+            Err(err) => {
+                println!("Error reading live files of {}: {:?}", self.profile.src_path, err);
+                return None;
+            }
+        };
+        self.backup_list.lock().unwrap().iter().find(|b| {
+            let checksums: Vec<(String, String)> = b.checksums.iter().filter(|(file, _)| filter.matches(file)).cloned().collect();
+            !!backup::hash_list_cmp(&live_hashes, &checksums)
+        }).cloned()
+    }
+
+    /// Advances the screenshot and backup state machine. Returns what happened, if anything.
+    pub fn tick(&self) -> Option<EngineEvent> {
+        match read_rwlock_or(&self.state.backup_state, BackupState::Idle) {
+            BackupState::Idle => {},
+            BackupState::Busy => return None,
+            BackupState::Finished => return self.finish_up_backup(),
+        }
+
+        if self.watcher.check_source() || self.watcher.check_native_events() {
+            return Some(EngineEvent::WatcherHealthChanged(self.watcher.health()));
+        }
+        if self.watcher.health() == WatcherHealth::SourceMissing {
+            return None;
+        }
+
+        if !self.watcher.has_changes() {
+            return None;
+        }
+
+        if self.profile.screenshots {
+            match read_rwlock_or(&self.state.screenshot_state, ScreenshotState::Idle) {
+                ScreenshotState::Idle => {
+                    println!("Taking screenshot");
+                    write_to_rwlock(&self.state.screenshot_state, ScreenshotState::Busy);
+                    let state = self.state.clone();
+                    let path = self.screenshot_path();
+                    std::thread::spawn(move || {
+                        match screenshot::create_screenshot(&path) {
+                            Ok(_) => write_to_rwlock(&state.screenshot_state, ScreenshotState::Finished),
+                            Err(err) => {
+                                write_to_rwlock(&state.screenshot_error, format!("{}", err));
+                                write_to_rwlock(&state.screenshot_state, ScreenshotState::Error);
+                            }
+                        }
+                    });
+                    return None;
+                },
+                ScreenshotState::Busy => return None,
+                ScreenshotState::Error => {
+                    write_to_rwlock(&self.state.screenshot_state, ScreenshotState::Finished);
+                    let error = read_rwlock_or(&self.state.screenshot_error, String::new());
+                    return Some(EngineEvent::ScreenshotFailed(error));
+                },
+                ScreenshotState::Finished => {},
+            }
+        }
+
+        // Wait for the quiet period, then until the files stopped changing for a few polls
+        let poll_state = read_rwlock_or(&self.state.poll_state, PollState::Idle);
+        if let PollState::Busy = poll_state {
+            return None;
+        }
+        write_to_rwlock(&self.state.poll_state, PollState::Idle);
+        let now = chrono::Utc::now().timestamp_millis();
+        if now - self.watcher.latest_change() <= self.profile.quiet_period_millis as i64 {
+            // Also drops a stable result if the files changed while it was polled
+            self.state.stability.lock().unwrap().reset();
+        } else if let PollState::Stable = poll_state {
+            self.state.stability.lock().unwrap().reset();
+            println!("Creating backup");
+            self.start_backup(self.profile.screenshots);
+        } else {
+            self.start_stability_poll();
+        }
+
+        None
+    }
+
+    /// Hashes the live files for the next poll of the [`StabilityDetector`] on a worker thread.
+    /// The result is picked up by the next [`BackupEngine::tick`].
+    fn start_stability_poll(&self) {
+        write_to_rwlock(&self.state.poll_state, PollState::Busy);
+        let src_path = self.profile.src_path.clone();
+        let filter = self.profile.file_filter();
+        let required_polls = self.profile.stable_polls;
+        let hash_cache = self.hash_cache.clone();
+        let state = self.state.clone();
+
+        std::thread::spawn(move || {
+            let stable = state.stability.lock().unwrap().poll(&src_path, &filter, &mut hash_cache.lock().unwrap(), required_polls);
+            write_to_rwlock(&state.poll_state, if stable { PollState::Stable } else { PollState::Idle });
+        });
+    }
+
+    /// Classifies the current state of the source folder and takes the matching backup on a
+    /// worker thread. The result is reported by the next [`BackupEngine::tick`]. Returns
+    /// `false` if a backup is already in progress.
+    pub fn start_backup(&self, copy_screenshot: bool) -> bool {
+        match read_rwlock_or(&self.state.backup_state, BackupState::Idle) {
+            BackupState::Idle => {},
+            _ => return false,
+        }
+        write_to_rwlock(&self.state.backup_state, BackupState::Busy);
+
+        let profile = self.profile.clone();
+        if profile.src_path.is_empty() || profile.dst_path.is_empty() {
+            println!("Source or destination path is empty");
+            write_to_rwlock(&self.state.backup_error, String::new());
+            write_to_rwlock(&self.state.backup_name, String::new());
+            write_to_rwlock(&self.state.backup_state, BackupState::Finished);
+            return true;
+        }
+
+        let latest = backup::latest_non_temp(&self.backups());
+        let screenshot = if copy_screenshot { Some(self.screenshot_path()) } else { None };
+        let hash_cache = self.hash_cache.clone();
+        let state = self.state.clone();
+
+        std::thread::spawn(move || {
+            let live_hashes = if profile.manual_save_detection {
+                match backup::create_hash_list(&profile.src_path, &profile.file_filter(), &mut hash_cache.lock().unwrap()) {
+                    Ok(live_hashes) => live_hashes,
+                    Err(err) => {
+                        if let Some(screenshot) = &screenshot {
+                            std::fs::remove_file(screenshot).unwrap_or_default();
+                        }
+                        write_to_rwlock(&state.backup_error, format!("Error reading live files: {}", err));
+                        write_to_rwlock(&state.backup_name, String::new());
+                        write_to_rwlock(&state.backup_state, BackupState::Finished);
+                        return;
+                    }
+                }
+            } else {
+                vec![]
+            };
+            let action = backup::classify_backup(&profile, &live_hashes, latest.as_ref());
+            match backup::run_backup_action(action, &profile, screenshot.as_ref()) {
+                Ok(outcome) => {
+                    write_to_rwlock(&state.backup_error, String::new());
+                    write_to_rwlock(&state.backup_name, outcome.name.unwrap_or_default());
+                    write_to_rwlock(&state.prune_report, outcome.pruned);
+                },
+                Err(err) => {
+                    write_to_rwlock(&state.backup_error, format!("{}", err));
+                    write_to_rwlock(&state.backup_name, String::new());
+                    write_to_rwlock(&state.prune_report, Default::default());
+                }
+            }
+            write_to_rwlock(&state.backup_state, BackupState::Finished);
+        });
+
+        true
+    }
+
+    /// Backups that were pruned after the last backup taken by this engine.
+    pub fn last_prune_report(&self) -> retention::PruneReport {
+        read_rwlock_or(&self.state.prune_report, Default::default())
+    }
+
+    fn finish_up_backup(&self) -> Option<EngineEvent> {
+        println!("Finishing up");
+        let error = read_rwlock_or(&self.state.backup_error, String::new());
+        let backup_name = read_rwlock_or(&self.state.backup_name, String::new());
+
+        write_to_rwlock(&self.state.backup_state, BackupState::Idle);
+        write_to_rwlock(&self.state.screenshot_state, ScreenshotState::Idle);
+        self.watcher.clear_changes();
+
+        if !error.is_empty() {
+            Some(EngineEvent::BackupFailed(error))
+        } else if !backup_name.is_empty() {
+            match backup::get_meta_for_backup(&self.profile.dst_path, &backup_name) {
+                Ok(_) => {
+                    let _ = self.refresh_backups();
+                    Some(EngineEvent::BackupCreated(backup_name))
+                },
+                Err(err) => Some(EngineEvent::BackupFailed(format!("Error reading backup meta: {:?}", err))),
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Restores `backup` into the source folder while ignoring the resulting file events.
+    /// The live files are captured as pre-restore snapshot first.
+    pub fn restore(&self, backup: &SavegameMeta) -> Result<(), anyhow::Error> {
+        self.watcher.set_paused(true);
+        let result = backup::load_backup(&self.profile.src_path, &self.profile.dst_path, backup, &self.profile.file_filter(), &mut self.hash_cache.lock().unwrap());
+        self.watcher.set_paused(false);
+        result
+    }
+
+    /// Restores only some files of a backup while ignoring the resulting file events.
+    pub fn restore_files(&self, backup: &SavegameMeta, files: &[String]) -> Result<(), anyhow::Error> {
+        self.watcher.set_paused(true);
+        let result = backup::load_backup_files(&self.profile.src_path, &self.profile.dst_path, backup, files, &mut self.hash_cache.lock().unwrap());
+        self.watcher.set_paused(false);
+        result
+    }
+
+    /// Restores the snapshot taken before the last restore while ignoring the resulting file events.
+    pub fn undo_last_restore(&self) -> Result<SavegameMeta, anyhow::Error> {
+        self.watcher.set_paused(true);
+        let result = backup::undo_last_restore(&self.profile.src_path, &self.profile.dst_path, &self.profile.file_filter(), &mut self.hash_cache.lock().unwrap());
+        self.watcher.set_paused(false);
+        result
+    }
+
+    pub fn verify_backup(&self, backup: &SavegameMeta) -> Result<backup::VerifyReport, anyhow::Error> {
+        backup::verify_backup(&self.profile.dst_path, backup)
+    }
+
+    /// Pins or unpins a backup, so rotation and pruning leave it alone, and refreshes the
+    /// cached list of backups.
+    pub fn set_pinned(&self, backup_name: &String, pinned: bool) -> Result<SavegameMeta, anyhow::Error> {
+        let meta = backup::set_pinned(&self.profile.dst_path, backup_name, pinned)?;
+        let _ = self.refresh_backups();
+        Ok(meta)
+    }
+
+    /// Replaces notes and tags of a backup and refreshes the cached list of backups.
+    pub fn set_annotations(&self, backup_name: &String, notes: &String, tags: &Vec<String>) -> Result<SavegameMeta, anyhow::Error> {
+        let meta = backup::set_annotations(&self.profile.dst_path, backup_name, notes, tags)?;
+        let _ = self.refresh_backups();
+        Ok(meta)
+    }
+
+    /// Compares the files of two backups of the profile.
+    pub fn diff_backups(&self, old: &SavegameMeta, new: &SavegameMeta) -> Result<diff::BackupDiff, anyhow::Error> {
+        diff::diff_backups(&self.profile.dst_path, old, new)
+    }
+
+    /// Compares the files of a backup with the live files in the source folder.
+    pub fn diff_with_live(&self, backup: &SavegameMeta) -> Result<diff::BackupDiff, anyhow::Error> {
+        diff::diff_with_live(&self.profile.src_path, &self.profile.dst_path, backup, &self.profile.file_filter(), &mut self.hash_cache.lock().unwrap())
+    }
+
+    /// Compares a single file of two backups byte by byte.
+    pub fn hex_diff_backups(&self, old: &SavegameMeta, new: &SavegameMeta, file: &String, options: &hexdiff::HexDiffOptions) -> Result<hexdiff::HexDiff, anyhow::Error> {
+        hexdiff::diff_backup_files(&self.profile.dst_path, old, new, file, options)
+    }
+
+    /// Compares a single file of a backup byte by byte with the live file.
+    pub fn hex_diff_with_live(&self, backup: &SavegameMeta, file: &String, options: &hexdiff::HexDiffOptions) -> Result<hexdiff::HexDiff, anyhow::Error> {
+        hexdiff::diff_file_with_live(&self.profile.src_path, &self.profile.dst_path, backup, file, options)
+    }
+
+    /// Backups of the profile that are left out of the list because their meta file can't be read.
+    pub fn broken_backups(&self) -> Result<Vec<repair::BrokenBackup>, anyhow::Error> {
+        repair::find_broken_backups(&self.profile.dst_path)
+    }
+
+    /// Rebuilds the meta file of every backup that has none or a broken one and refreshes the
+    /// cached list of backups.
+    pub fn repair_backups(&self) -> Result<repair::RepairReport, anyhow::Error> {
+        let report = repair::repair_backups(&self.profile.dst_path)?;
+        let _ = self.refresh_backups();
+        Ok(report)
+    }
+
+    pub fn rename_backup(&self, old_name: &String, new_name: &String) -> Result<(), anyhow::Error> {
+        backup::rename_backup(&self.profile.dst_path, old_name, new_name)
+    }
+
+    pub fn recycle_backup(&self, backup_name: &String) -> Result<(), trash::Error> {
+        backup::recycle_backup(&self.profile.dst_path, backup_name)
+    }
+
+    /// Stops watching, keeps the latest temporary save as exit save and removes a screenshot
+    /// that was not picked up by a backup.
+    pub fn shutdown(&mut self) {
+        self.stop_watcher();
+        backup::deal_with_exit_save(&self.profile.dst_path);
+
+        let screenshot_file = self.screenshot_path();
+        if screenshot_file.exists() && screenshot_file.is_file() {
+            std::fs::remove_file(screenshot_file).unwrap_or_default();
+        }
+    }
+}
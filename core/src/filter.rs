@@ -0,0 +1,181 @@
+use std::path::Path;
+
+/// Decides which files of the source folder are part of a savegame, based on the include and
+/// exclude glob patterns of a profile.
+///
+/// Patterns are matched against the path relative to the source folder, using `/` as
+/// separator and ignoring case. `*` matches anything but `/`, `**` matches anything, `?`
+/// matches a single character. Patterns without `/` match the file name in every folder and a
+/// trailing `/` matches everything in that folder.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileFilter {
+    include: Vec<Vec<char>>,
+    exclude: Vec<Vec<char>>,
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        Self {
+            include: include.iter().map(String::as_str).filter_map(compile).collect(),
+            exclude: exclude.iter().map(String::as_str).filter_map(compile).collect(),
+        }
+    }
+
+    /// Whether the filter lets every file through.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether the file at `relative_path` belongs to the savegame. Without include patterns
+    /// every file is included. Exclude patterns win over include patterns.
+    pub fn matches(&self, relative_path: &str) -> bool {
+        let path: Vec<char> = relative_path.to_lowercase().replace('\\', "/").trim_start_matches('/').chars().collect();
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, &path)))
+            && !self.exclude.iter().any(|p| glob_match(p, &path))
+    }
+
+    /// Like [`FileFilter::matches`] for an absolute `path` below `root`. Paths outside of
+    /// `root` never match.
+    pub fn matches_path(&self, root: &Path, path: &Path) -> bool {
+        match path.strip_prefix(root) {
+            Ok(relative_path) => self.matches(&relative_path.to_string_lossy()),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Splits a comma separated list of patterns as entered by the user. Empty patterns are dropped.
+pub fn parse_patterns(text: &str) -> Vec<String> {
+    text.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()).map(|p| p.to_owned()).collect()
+}
+
+fn compile(pattern: &str) -> Option<Vec<char>> {
+    let mut pattern = pattern.trim().to_lowercase().replace('\\', "/");
+    if pattern.is_empty() {
+        return None;
+    }
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    if !anchored {
+        pattern.insert_str(0, "**/");
+    }
+    Some(pattern.trim_start_matches('/').chars().collect())
+}
+
+fn glob_match(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `**/` also matches no folder at all
+            if rest.first() == Some(&'/') && glob_match(&rest[1..], path) {
+                return true;
+            }
+            (0..=path.len()).any(|i| glob_match(rest, &path[i..]))
+        },
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=path.len() {
+                if glob_match(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        },
+        Some('?') => path.first().is_some_and(|c| *c != '/') && glob_match(&pattern[1..], &path[1..]),
+        Some(c) => path.first() == Some(c) && glob_match(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> FileFilter {
+        let to_strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+        FileFilter::new(&to_strings(include), &to_strings(exclude))
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let empty = filter(&[], &[" ", ""]);
+        assert!(empty.is_empty());
+        assert!(empty.matches("slot1.sav"));
+        assert!(empty.matches("profile/settings.ini"));
+    }
+
+    #[test]
+    fn star_stays_within_a_folder() {
+        let sav = filter(&["saves/*.sav"], &[]);
+        assert!(sav.matches("saves/slot1.sav"));
+        assert!(sav.matches("saves/.sav"));
+        assert!(!sav.matches("saves/old/slot1.sav"));
+        assert!(!sav.matches("saves/slot1.sav.bak"));
+    }
+
+    #[test]
+    fn double_star_crosses_folders() {
+        let sav = filter(&["saves/**/*.sav"], &[]);
+        assert!(sav.matches("saves/slot1.sav"));
+        assert!(sav.matches("saves/old/2024/slot1.sav"));
+        assert!(!sav.matches("other/slot1.sav"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        let slots = filter(&["slot?.sav"], &[]);
+        assert!(slots.matches("slot1.sav"));
+        assert!(!slots.matches("slot.sav"));
+        assert!(!slots.matches("slot10.sav"));
+        assert!(!filter(&["a?b"], &[]).matches("a/b"));
+    }
+
+    #[test]
+    fn unanchored_patterns_match_in_every_folder() {
+        let sav = filter(&["*.SAV"], &[]);
+        assert!(sav.matches("slot1.sav"));
+        assert!(sav.matches("profiles\\steam\\slot1.sav"));
+        assert!(!sav.matches("slot1.sav/readme.txt"));
+
+        let cache = filter(&[], &["cache/"]);
+        assert!(!cache.matches("cache/shader.bin"));
+        assert!(!cache.matches("profile/cache/shader.bin"));
+        assert!(cache.matches("cache.bin"));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_root() {
+        let slots = filter(&["/slot1.sav", "profile/*.ini"], &[]);
+        assert!(slots.matches("slot1.sav"));
+        assert!(!slots.matches("backup/slot1.sav"));
+        assert!(slots.matches("profile/settings.ini"));
+        assert!(!slots.matches("old/profile/settings.ini"));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let sav = filter(&["*.sav", "*.log"], &["autosave*.sav", "*.log"]);
+        assert!(sav.matches("slot1.sav"));
+        assert!(!sav.matches("autosave1.sav"));
+        assert!(!sav.matches("game.log"));
+        assert!(!sav.matches("settings.ini"));
+    }
+
+    #[test]
+    fn matches_paths_below_the_root_only() {
+        let sav = filter(&["*.sav"], &[]);
+        let root = Path::new("/games/saves");
+        assert!(sav.matches_path(root, Path::new("/games/saves/slot1.sav")));
+        assert!(!sav.matches_path(root, Path::new("/games/other/slot1.sav")));
+    }
+
+    #[test]
+    fn parses_comma_separated_patterns() {
+        assert_eq!(parse_patterns(" *.sav, ,cache/ ,"), vec![String::from("*.sav"), String::from("cache/")]);
+    }
+}
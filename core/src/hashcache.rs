@@ -0,0 +1,240 @@
+use crate::*;
+
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+
+/// Name of the cache file in the backup folder. The leading dot keeps it out of the backup list.
+pub const CACHE_FILE: &str = ".hash_cache.json";
+
+/// Entries are only trusted if the file was last modified at least this long before it was
+/// hashed. File systems with a coarse timestamp resolution (FAT uses two seconds) would
+/// otherwise not tell apart two writes within the same tick and the cache would keep the hash
+/// of the first one.
+const RACY_MILLIS: i64 = 2_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified: i64,
+    hashed_at: i64,
+    checksum: String,
+}
+
+impl CacheEntry {
+    fn is_valid_for(&self, size: u64, modified: i64) -> bool {
+        self.size == size && self.modified == modified && self.hashed_at - self.modified >= RACY_MILLIS
+    }
+}
+
+/// Checksums of source files by path, size and modification time, so unchanged files are not
+/// hashed again on every refresh. The entries are stored in [`CACHE_FILE`] in the backup
+/// folder. Without a backup folder the cache only lives in memory.
+#[derive(Default)]
+pub struct HashCache {
+    path: Option<PathBuf>,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl HashCache {
+    /// Reads the cache of the backup folder `dst_path`. A missing or broken cache file results
+    /// in an empty cache.
+    pub fn open(dst_path: &String) -> Self {
+        let dst_pathbuf = PathBuf::from(dst_path);
+        if dst_path.is_empty() || !dst_pathbuf.is_dir() {
+            return Default::default();
+        }
+
+        let path = dst_pathbuf.join(CACHE_FILE);
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        };
+        Self { path: Some(path), entries }
+    }
+
+    /// Returns the blake3 checksum of every file in `files`, given as pairs of relative and
+    /// absolute path like [`backup::list_source_files`] returns them for the folder `root`.
+    ///
+    /// Files whose size and modification time did not change since they were last hashed are
+    /// taken from the cache, all others are hashed in parallel. Entries of files below `root`
+    /// that are not in `files` anymore are dropped.
+    pub fn hash_files(&mut self, root: &Path, files: &Vec<(String, PathBuf)>) -> Result<Vec<(String, String)>, anyhow::Error> {
+        let mut stamps: Vec<(String, u64, i64)> = vec![];
+        for (_, path) in files {
+            let (size, modified) = file_stamp(path)?;
+            stamps.push((cache_key(path), size, modified));
+        }
+
+        let mut checksums: Vec<Option<String>> = stamps.iter().map(|(key, size, modified)| {
+            self.entries.get(key).filter(|entry| entry.is_valid_for(*size, *modified)).map(|entry| entry.checksum.clone())
+        }).collect();
+
+        let missing: Vec<usize> = (0..files.len()).filter(|i| checksums[*i].is_none()).collect();
+        let hashed_at = chrono::Utc::now().timestamp_millis();
+        let paths: Vec<PathBuf> = missing.iter().map(|i| files[*i].1.clone()).collect();
+        for (i, checksum) in missing.iter().zip(hash_parallel(&paths)) {
+            let checksum = checksum?;
+            let (key, size, modified) = &stamps[*i];
+            self.entries.insert(key.clone(), CacheEntry { size: *size, modified: *modified, hashed_at, checksum: checksum.clone() });
+            checksums[*i] = Some(checksum);
+        }
+
+        // Forget files of this folder that are gone, so the cache does not grow forever
+        let root = std::fs::canonicalize(root).unwrap_or(root.to_path_buf());
+        let current: HashSet<&String> = stamps.iter().map(|(key, _, _)| key).collect();
+        let count = self.entries.len();
+        self.entries.retain(|key, _| current.contains(key) || !Path::new(key).starts_with(&root));
+
+        if !missing.is_empty() || self.entries.len() != count {
+            self.write();
+        }
+
+        Ok(files.iter().zip(checksums).map(|((relative_path, _), checksum)| (relative_path.clone(), checksum.unwrap_or_default())).collect())
+    }
+
+    fn write(&self) {
+        if let Some(path) = &self.path {
+            if let Err(err) = write_entries(path, &self.entries) {
+                println!("Error writing hash cache {}: {:?}", path.display(), err);
+            }
+        }
+    }
+}
+
+fn write_entries(path: &PathBuf, entries: &HashMap<String, CacheEntry>) -> Result<(), anyhow::Error> {
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_string(entries)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Returns the blake3 checksum of every file in `files` without looking at any cache. Used for
+/// the checksums stored in a backup, which must not depend on modification times being reliable.
+pub fn hash_files(files: &[(String, PathBuf)]) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let paths: Vec<PathBuf> = files.iter().map(|(_, path)| path.clone()).collect();
+    files.iter().zip(hash_parallel(&paths)).map(|((relative_path, _), checksum)| Ok((relative_path.clone(), checksum?))).collect()
+}
+
+/// Size and modification time of `path` in milliseconds.
+fn file_stamp(path: &Path) -> Result<(u64, i64), std::io::Error> {
+    let metadata = path.metadata()?;
+    Ok((metadata.len(), metadata.modified().ok().and_then(millis_from_system_time).unwrap_or(0)))
+}
+
+fn cache_key(path: &Path) -> String {
+    std::fs::canonicalize(path).unwrap_or(path.to_path_buf()).to_string_lossy().to_string()
+}
+
+/// Hashes `paths` on as many threads as there are cores. The results are in the order of `paths`.
+fn hash_parallel(paths: &[PathBuf]) -> Vec<Result<String, anyhow::Error>> {
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(paths.len()).max(1);
+    let chunk_size = paths.len().div_ceil(threads).max(1);
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = paths.chunks(chunk_size).map(|chunk| {
+            (chunk.len(), scope.spawn(move || chunk.iter().map(fhc::file_blake3).collect::<Vec<_>>()))
+        }).collect();
+        workers.into_iter().flat_map(|(len, worker)| {
+            worker.join().unwrap_or_else(|_| (0..len).map(|_| Err(anyhow::anyhow!("Hashing thread panicked"))).collect())
+        }).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// Writes `content` to `path` and sets its modification time `age` into the past.
+    fn write_file(path: &Path, content: &str, age: Duration) {
+        std::fs::write(path, content).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn files_of(root: &Path, names: &[&str]) -> Vec<(String, PathBuf)> {
+        names.iter().map(|name| (name.to_string(), root.join(name))).collect()
+    }
+
+    fn checksum_of(path: &Path) -> String {
+        fhc::file_blake3(path).unwrap()
+    }
+
+    #[test]
+    fn reuses_entries_of_unchanged_files() {
+        let dir = test_dir("hashcache_reuse");
+        let file = dir.join("slot1.sav");
+        write_file(&file, "first", Duration::from_secs(3600));
+        let original = checksum_of(&file);
+        let modified = file.metadata().unwrap().modified().unwrap();
+
+        let mut cache = HashCache::default();
+        assert_eq!(cache.hash_files(&dir, &files_of(&dir, &["slot1.sav"])).unwrap()[0].1, original);
+
+        // Same size and modification time, so the content is not read again
+        std::fs::write(&file, "other").unwrap();
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+        assert_eq!(cache.hash_files(&dir, &files_of(&dir, &["slot1.sav"])).unwrap()[0].1, original);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_files_again_when_size_or_mtime_changed() {
+        let dir = test_dir("hashcache_invalidate");
+        let file = dir.join("slot1.sav");
+        write_file(&file, "first", Duration::from_secs(3600));
+        let mut cache = HashCache::default();
+        cache.hash_files(&dir, &files_of(&dir, &["slot1.sav"])).unwrap();
+
+        write_file(&file, "longer content", Duration::from_secs(3600));
+        assert_eq!(cache.hash_files(&dir, &files_of(&dir, &["slot1.sav"])).unwrap()[0].1, checksum_of(&file));
+
+        write_file(&file, "same size here", Duration::from_secs(1800));
+        assert_eq!(cache.hash_files(&dir, &files_of(&dir, &["slot1.sav"])).unwrap()[0].1, checksum_of(&file));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn does_not_trust_entries_of_files_modified_while_hashing() {
+        let entry = CacheEntry { size: 5, modified: 10_000, hashed_at: 11_000, checksum: String::from("abc") };
+        assert!(!entry.is_valid_for(5, 10_000));
+        assert!(CacheEntry { hashed_at: 12_000, ..entry.clone() }.is_valid_for(5, 10_000));
+        assert!(!CacheEntry { hashed_at: 12_000, ..entry.clone() }.is_valid_for(6, 10_000));
+        assert!(!CacheEntry { hashed_at: 12_000, ..entry }.is_valid_for(5, 10_001));
+    }
+
+    #[test]
+    fn stores_entries_and_drops_removed_files_of_the_hashed_folder_only() {
+        let dst = test_dir("hashcache_store");
+        let src = dst.join("saves");
+        let other = dst.join("other");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::create_dir(&other).unwrap();
+        for path in [src.join("slot1.sav"), src.join("slot2.sav"), other.join("slot1.sav")] {
+            write_file(&path, "content", Duration::from_secs(3600));
+        }
+
+        let dst_path = dst.to_string_lossy().to_string();
+        let mut cache = HashCache::open(&dst_path);
+        cache.hash_files(&src, &files_of(&src, &["slot1.sav", "slot2.sav"])).unwrap();
+        cache.hash_files(&other, &files_of(&other, &["slot1.sav"])).unwrap();
+        assert_eq!(cache.entries.len(), 3);
+
+        std::fs::remove_file(src.join("slot2.sav")).unwrap();
+        cache.hash_files(&src, &files_of(&src, &["slot1.sav"])).unwrap();
+
+        let reopened = HashCache::open(&dst_path);
+        assert_eq!(reopened.entries, cache.entries);
+        assert!(reopened.entries.contains_key(&cache_key(&src.join("slot1.sav"))));
+        assert!(reopened.entries.contains_key(&cache_key(&other.join("slot1.sav"))));
+        assert_eq!(reopened.entries.len(), 2);
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn fails_for_missing_files() {
+        let dir = test_dir("hashcache_missing");
+        assert!(HashCache::default().hash_files(&dir, &files_of(&dir, &["slot1.sav"])).is_err());
+        assert!(hash_files(&files_of(&dir, &["slot1.sav"])).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
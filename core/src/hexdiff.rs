@@ -0,0 +1,290 @@
+use crate::*;
+use backup::SavegameMeta;
+
+use std::path::PathBuf;
+use serde::{Serialize, Serializer};
+
+/// Number of bytes shown per line of a hex view.
+pub const BYTES_PER_LINE: usize = 16;
+
+/// How much of a byte diff is shown.
+#[derive(Clone, Debug)]
+pub struct HexDiffOptions {
+    /// Unchanged lines shown before and after every changed line.
+    pub context_lines: usize,
+    /// The view stops after this many lines, so completely different files don't produce
+    /// millions of rows. `0` means no limit.
+    pub max_lines: usize,
+}
+
+impl Default for HexDiffOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 2,
+            max_lines: 2000,
+        }
+    }
+}
+
+/// Bytes that differ between the old and the new file. Bytes are compared by position, so
+/// the offset is the same in both files. Bytes past the end of the shorter file count as
+/// changed.
+#[derive(Clone, Debug, Serialize)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HexRow {
+    /// Up to [`BYTES_PER_LINE`] bytes of both files starting at `offset`. A side is shorter or
+    /// empty where its file ends.
+    Line {
+        offset: u64,
+        #[serde(serialize_with = "serialize_hex")]
+        old: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        new: Vec<u8>,
+        changed: bool,
+    },
+    /// Unchanged bytes that are left out of the view.
+    Collapsed {
+        offset: u64,
+        length: u64,
+    },
+}
+
+/// Byte level comparison of one file in two backups or in a backup and the live files.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HexDiff {
+    /// Path of the file relative to the savegame folder.
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    /// Every changed byte range, also the ones beyond `max_lines`.
+    pub ranges: Vec<ByteRange>,
+    /// Changed lines with their context. Unchanged regions in between are collapsed.
+    pub rows: Vec<HexRow>,
+    /// Whether rows were left out because of `max_lines`.
+    pub truncated: bool,
+}
+
+impl HexDiff {
+    /// Number of bytes that differ.
+    pub fn changed_bytes(&self) -> u64 {
+        self.ranges.iter().map(|r| r.length).sum()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.ranges.is_empty() {
+            return String::from("No differences");
+        }
+        let sizes = if self.old_size == self.new_size {
+            diff::format_size(self.new_size)
+        } else {
+            format!("{} -> {}", diff::format_size(self.old_size), diff::format_size(self.new_size))
+        };
+        format!("{} byte(s) changed in {} range(s), {}", self.changed_bytes(), self.ranges.len(), sizes)
+    }
+}
+
+impl std::fmt::Display for HexDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}: {}", self.path, self.summary())?;
+        for row in &self.rows {
+            match row {
+                HexRow::Line { offset, old, new, changed } => {
+                    writeln!(f, "{} {:08x}  {}  |  {}", if *changed { '~' } else { ' ' }, offset, hex_bytes(old), hex_bytes(new))?;
+                    if *changed {
+                        writeln!(f, "  {:8}  {}     {}", "", " ".repeat(BYTES_PER_LINE * 3 - 1), change_markers(old, new))?;
+                    }
+                },
+                HexRow::Collapsed { offset, length } => {
+                    writeln!(f, "  ... {} unchanged at {:08x} ...", diff::format_size(*length), offset)?;
+                },
+            }
+        }
+        if self.truncated {
+            writeln!(f, "  ... more changes not shown ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Bytes as space separated hex pairs, padded to the width of a full line.
+pub fn hex_bytes(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{:width$}", hex.join(" "), width = BYTES_PER_LINE * 3 - 1)
+}
+
+/// `^^` below every byte of a line that differs between `old` and `new`.
+pub fn change_markers(old: &[u8], new: &[u8]) -> String {
+    let length = old.len().max(new.len());
+    let markers: Vec<&str> = (0..length).map(|i| if old.get(i) == new.get(i) { "  " } else { "^^" }).collect();
+    markers.join(" ").trim_end().to_owned()
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// Compares `old` and `new` byte by byte.
+pub fn diff_bytes(path: &str, old: &[u8], new: &[u8], options: &HexDiffOptions) -> HexDiff {
+    let length = old.len().max(new.len());
+    let line_count = length.div_ceil(BYTES_PER_LINE);
+    let line = |bytes: &[u8], i: usize| -> Vec<u8> {
+        let start = (i * BYTES_PER_LINE).min(bytes.len());
+        let end = ((i + 1) * BYTES_PER_LINE).min(bytes.len());
+        bytes[start..end].to_vec()
+    };
+
+    let mut ranges: Vec<ByteRange> = vec![];
+    let mut changed_lines: Vec<usize> = vec![];
+    for i in 0..line_count {
+        let start = i * BYTES_PER_LINE;
+        let end = (start + BYTES_PER_LINE).min(length);
+        if old.get(start..end) == new.get(start..end) {
+            continue;
+        }
+
+        changed_lines.push(i);
+        for offset in start..end {
+            if old.get(offset) == new.get(offset) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.offset + range.length == offset as u64 => range.length += 1,
+                _ => ranges.push(ByteRange { offset: offset as u64, length: 1 }),
+            }
+        }
+    }
+
+    let mut rows: Vec<HexRow> = vec![];
+    let mut truncated = false;
+    let mut shown_lines = 0;
+    // First line that is not shown yet
+    let mut next_line = 0;
+    for &changed_line in &changed_lines {
+        let first = changed_line.saturating_sub(options.context_lines).max(next_line);
+        let last = (changed_line + options.context_lines).min(line_count - 1);
+        if first > last {
+            continue;
+        }
+
+        if first > next_line {
+            rows.push(HexRow::Collapsed { offset: (next_line * BYTES_PER_LINE) as u64, length: ((first - next_line) * BYTES_PER_LINE) as u64 });
+        }
+        for i in first..=last {
+            if options.max_lines > 0 && shown_lines >= options.max_lines {
+                truncated = true;
+                break;
+            }
+            let (old_line, new_line) = (line(old, i), line(new, i));
+            let changed = old_line != new_line;
+            rows.push(HexRow::Line { offset: (i * BYTES_PER_LINE) as u64, old: old_line, new: new_line, changed });
+            shown_lines += 1;
+            next_line = i + 1;
+        }
+        if truncated {
+            break;
+        }
+    }
+    if !truncated && next_line > 0 && next_line < line_count {
+        rows.push(HexRow::Collapsed { offset: (next_line * BYTES_PER_LINE) as u64, length: (length - next_line * BYTES_PER_LINE) as u64 });
+    }
+
+    HexDiff { path: path.to_string(), old_size: old.len() as u64, new_size: new.len() as u64, ranges, rows, truncated }
+}
+
+/// Compares `file` in backup `old` with the same file in backup `new`. A file missing on one
+/// side is compared as empty file.
+pub fn diff_backup_files(dst_path: &String, old: &SavegameMeta, new: &SavegameMeta, file: &String, options: &HexDiffOptions) -> Result<HexDiff, anyhow::Error> {
+    let old_content = backup::read_backup_file(dst_path, old, file)?;
+    let new_content = backup::read_backup_file(dst_path, new, file)?;
+    compare_contents(file, old_content, new_content, options)
+}
+
+/// Compares `file` in `backup` with the live file in `src_path`.
+pub fn diff_file_with_live(src_path: &String, dst_path: &String, backup: &SavegameMeta, file: &String, options: &HexDiffOptions) -> Result<HexDiff, anyhow::Error> {
+    let old_content = backup::read_backup_file(dst_path, backup, file)?;
+    let live_path = backup::relative_to_path(&PathBuf::from(src_path), file);
+    let new_content = if live_path.is_file() { Some(std::fs::read(live_path)?) } else { None };
+    compare_contents(file, old_content, new_content, options)
+}
+
+fn compare_contents(file: &String, old: Option<Vec<u8>>, new: Option<Vec<u8>>, options: &HexDiffOptions) -> Result<HexDiff, anyhow::Error> {
+    if old.is_none() && new.is_none() {
+        return Err(anyhow::anyhow!("{} exists on neither side", file));
+    }
+    Ok(diff_bytes(file, &old.unwrap_or_default(), &new.unwrap_or_default(), options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(context_lines: usize, max_lines: usize) -> HexDiffOptions {
+        HexDiffOptions { context_lines, max_lines }
+    }
+
+    fn bytes(length: usize) -> Vec<u8> {
+        (0..length).map(|i| i as u8).collect()
+    }
+
+    /// Offset, lengths of both sides and whether it changed for lines, `None` for collapsed rows.
+    fn lines(diff: &HexDiff) -> Vec<Option<(u64, usize, usize, bool)>> {
+        diff.rows.iter().map(|row| match row {
+            HexRow::Line { offset, old, new, changed } => Some((*offset, old.len(), new.len(), *changed)),
+            HexRow::Collapsed { .. } => None,
+        }).collect()
+    }
+
+    #[test]
+    fn equal_input_has_no_rows() {
+        let diff = diff_bytes("slot1.sav", &bytes(40), &bytes(40), &options(2, 0));
+        assert!(diff.ranges.is_empty());
+        assert!(diff.rows.is_empty());
+        assert!(!diff.truncated);
+        assert_eq!(diff.summary(), "No differences");
+
+        let empty = diff_bytes("slot1.sav", &[], &[], &options(2, 0));
+        assert!(empty.ranges.is_empty() && empty.rows.is_empty());
+    }
+
+    #[test]
+    fn bytes_past_the_shorter_input_are_changed() {
+        let diff = diff_bytes("slot1.sav", &bytes(20), &bytes(36), &options(2, 0));
+        assert_eq!((diff.old_size, diff.new_size), (20, 36));
+        assert_eq!(diff.ranges.len(), 1);
+        assert_eq!((diff.ranges[0].offset, diff.ranges[0].length), (20, 16));
+        assert_eq!(lines(&diff), vec![Some((0, 16, 16, false)), Some((16, 4, 16, true)), Some((32, 0, 4, true))]);
+    }
+
+    #[test]
+    fn partly_filled_last_line() {
+        let mut new = bytes(40);
+        new[38] = 0xff;
+        let diff = diff_bytes("slot1.sav", &bytes(40), &new, &options(0, 0));
+        assert_eq!(diff.changed_bytes(), 1);
+        assert_eq!(diff.ranges[0].offset, 38);
+        assert!(matches!(diff.rows[0], HexRow::Collapsed { offset: 0, length: 32 }));
+        assert_eq!(lines(&diff), vec![None, Some((32, 8, 8, true))]);
+    }
+
+    #[test]
+    fn collapses_unchanged_regions_and_stops_at_max_lines() {
+        let mut new = bytes(160);
+        new[0] = 0xff;
+        new[100] = 0xff;
+        let diff = diff_bytes("slot1.sav", &bytes(160), &new, &options(1, 0));
+        assert_eq!(lines(&diff), vec![Some((0, 16, 16, true)), Some((16, 16, 16, false)), None, Some((80, 16, 16, false)), Some((96, 16, 16, true)), Some((112, 16, 16, false)), None]);
+        assert!(matches!(diff.rows[2], HexRow::Collapsed { offset: 32, length: 48 }));
+        assert!(matches!(diff.rows[6], HexRow::Collapsed { offset: 128, length: 32 }));
+
+        let truncated = diff_bytes("slot1.sav", &bytes(160), &new, &options(1, 3));
+        assert!(truncated.truncated);
+        assert_eq!(truncated.ranges.len(), 2);
+        assert_eq!(lines(&truncated), vec![Some((0, 16, 16, true)), Some((16, 16, 16, false)), None, Some((80, 16, 16, false))]);
+    }
+}
@@ -0,0 +1,44 @@
+//! GUI independent core of the savegame manager.
+//!
+//! This crate contains everything needed to watch a savegame folder, create backups of it,
+//! rotate them and restore them again. The Windows GUI is just one frontend on top of it.
+//!
+//! The crate is split into the following modules:
+//! - [`profile`]: profiles as they are stored in the config file and loading/saving them
+//! - [`filter`]: which files of the source folder belong to the savegame
+//! - [`backup`]: creating, listing, comparing, restoring and deleting backups
+//! - [`hashcache`]: checksums of unchanged files are remembered between runs
+//! - [`schema`]: versions of the `meta.json` format and migrations between them
+//! - [`retention`]: which old backups are pruned and why
+//! - [`repair`]: rebuilding lost `meta.json` files and adopting folders copied in by hand
+//! - [`diff`]: which files were added, removed or changed between two backups
+//! - [`hexdiff`]: which bytes of a file changed between two backups
+//! - [`storage`]: how backed up files are stored, either as plain copies or deduplicated
+//! - [`archive`]: reading and writing backups stored as zip or tar.zst archives
+//! - [`watcher`]: watching the source folder for changes
+//! - [`screenshot`]: taking a screenshot to attach to the next backup
+//! - [`stability`]: waiting until the game finished writing its savegame
+//! - [`engine`]: [`BackupEngine`] ties all of the above together for a single profile
+//!
+//! Screenshots are captured with the `screenshots` crate which is enabled by the default
+//! `screenshots` feature. Disable it for headless builds.
+
+pub mod utils;
+pub mod profile;
+pub mod screenshot;
+pub mod filter;
+pub mod backup;
+pub mod hashcache;
+pub mod schema;
+pub mod retention;
+pub mod repair;
+pub mod diff;
+pub mod hexdiff;
+pub mod storage;
+pub mod archive;
+pub mod watcher;
+pub mod stability;
+pub mod engine;
+
+pub use utils::*;
+pub use engine::{BackupEngine, EngineEvent};
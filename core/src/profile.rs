@@ -0,0 +1,157 @@
+use crate::{filter::FileFilter, retention::RetentionPolicy, storage::StorageMode, watcher::WatcherBackend};
+
+use std::fs::File;
+
+use serde::{Deserialize, Serialize};
+
+/// Default location of the config file, relative to the working directory.
+pub const DATA_FILE: &str = "savegame_manager.json";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum ProfileIntervalUnit {
+    Seconds,
+    #[default]
+    Minutes,
+    Hours,
+}
+
+impl std::fmt::Display for ProfileIntervalUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Seconds => "seconds",
+            Self::Minutes => "minutes",
+            Self::Hours => "hours"
+        })
+    }
+}
+
+/// Config file layout of versions before profiles were introduced.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct OldSavegameManagerSettings {
+    source_path: String,
+    dest_path: String,
+    disable_screenshots: bool,
+}
+
+fn default_true() -> bool { true }
+
+/// A pair of a savegame folder and the folder its backups are stored in, plus the
+/// settings on how backups are taken.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavegameManagerProfile {
+    pub selected: bool,
+    pub name: String,
+    pub src_path: String,
+    pub dst_path: String,
+    #[serde(default = "default_true")] pub screenshots: bool,
+    #[serde(default = "default_true")] pub manual_save_detection: bool,
+    pub auto_saves_max: u16,
+    pub auto_saves_interval: u16,
+    pub auto_saves_interval_unit: ProfileIntervalUnit,
+    pub storage_mode: StorageMode,
+    pub retention: RetentionPolicy,
+    /// Rewrite `meta.json` files of older versions when backups are listed.
+    pub upgrade_meta_files: bool,
+    /// How changes of the source folder are noticed.
+    pub watcher_backend: WatcherBackend,
+    /// Milliseconds between two scans of the source folder when polling.
+    pub poll_interval_millis: u32,
+    /// Milliseconds without file events before the files are checked for stability.
+    pub quiet_period_millis: u32,
+    /// Checks in a row in which sizes and checksums of the files must stay the same before a
+    /// backup is taken.
+    pub stable_polls: u16,
+    /// Glob patterns of files that belong to the savegame. Empty means every file.
+    pub include_patterns: Vec<String>,
+    /// Glob patterns of files that are never backed up, restored or watched.
+    pub exclude_patterns: Vec<String>,
+}
+
+impl SavegameManagerProfile {
+    /// Time in milliseconds that has to pass since the last kept backup before a partial
+    /// change is stored as autosave instead of a temporary save.
+    pub fn auto_saves_interval_millis(&self) -> i64 {
+        crate::interval_duration(self.auto_saves_interval, &self.auto_saves_interval_unit)
+    }
+
+    /// Filter built from the include and exclude patterns.
+    pub fn file_filter(&self) -> FileFilter {
+        FileFilter::new(&self.include_patterns, &self.exclude_patterns)
+    }
+
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_interval_millis.max(100) as u64)
+    }
+}
+
+impl std::fmt::Display for SavegameManagerProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Default for SavegameManagerProfile {
+    fn default() -> Self {
+        Self {
+            selected: Default::default(),
+            name: Default::default(),
+            src_path: Default::default(),
+            dst_path: Default::default(),
+            screenshots: true,
+            manual_save_detection: true,
+            auto_saves_max: 12,
+            auto_saves_interval: 5,
+            auto_saves_interval_unit: Default::default(),
+            storage_mode: Default::default(),
+            retention: Default::default(),
+            upgrade_meta_files: false,
+            watcher_backend: Default::default(),
+            poll_interval_millis: 2_000,
+            quiet_period_millis: 1_000,
+            stable_polls: 2,
+            include_patterns: vec![],
+            exclude_patterns: vec![],
+        }
+    }
+}
+
+/// Reads all profiles from the config file at `path`.
+///
+/// Config files written by versions before profiles existed are converted into a single
+/// profile called "Default". If the file can not be opened the returned error wraps the
+/// [`std::io::Error`], so callers can tell a missing config file apart from a broken one.
+pub fn load_profiles(path: &str) -> Result<Vec<SavegameManagerProfile>, anyhow::Error> {
+    let file = File::open(path)?;
+    match serde_json::from_reader::<File, Vec<SavegameManagerProfile>>(file) {
+        Ok(json) => Ok(json),
+        Err(err) => {
+            match serde_json::from_reader::<File, OldSavegameManagerSettings>(File::open(path)?) {
+                Ok(json) => {
+                    Ok(vec![SavegameManagerProfile {
+                        name: "Default".to_owned(),
+                        selected: true,
+                        src_path: json.source_path,
+                        dst_path: json.dest_path,
+                        screenshots: !json.disable_screenshots,
+                        ..Default::default()
+                    }])
+                },
+                Err(_) => Err(anyhow::anyhow!("Unable to parse config file. {:?}", err)),
+            }
+        }
+    }
+}
+
+/// Writes all profiles to the config file at `path`.
+pub fn save_profiles(path: &str, profiles: &[SavegameManagerProfile]) -> Result<(), anyhow::Error> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, profiles)?;
+    Ok(())
+}
+
+/// Finds a profile by its name.
+pub fn find_profile<'a>(profiles: &'a [SavegameManagerProfile], name: &str) -> Option<&'a SavegameManagerProfile> {
+    profiles.iter().find(|p| p.name == name)
+}
@@ -0,0 +1,181 @@
+use crate::*;
+use backup::{BackupKind, SavegameMeta};
+use storage::StorageMode;
+
+use std::{path::{Path, PathBuf}, time::SystemTime};
+use serde::Serialize;
+
+/// Format of the date in the names of backups created by the savegame manager.
+const NAME_DATE_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+const NAME_DATE_LENGTH: usize = 19;
+
+/// A backup whose `meta.json` is missing or can't be read, so it does not show up in
+/// [`backup::look_for_backups`]. Plain folders copied into the destination by hand are
+/// broken backups as well.
+#[derive(Clone, Debug, Serialize)]
+pub struct BrokenBackup {
+    pub name: String,
+    /// Why the meta file could not be read or rebuilt.
+    pub error: String,
+}
+
+/// Result of [`repair_backups`].
+#[derive(Clone, Default, Serialize)]
+pub struct RepairReport {
+    pub repaired: Vec<String>,
+    pub failed: Vec<BrokenBackup>,
+}
+
+/// Every backup in `dst_path` without a readable `meta.json`.
+pub fn find_broken_backups(dst_path: &String) -> Result<Vec<BrokenBackup>, anyhow::Error> {
+    let mut broken: Vec<BrokenBackup> = vec![];
+    for backup_name in backup::list_backup_names(dst_path)? {
+        if let Err(err) = backup::get_meta_for_backup(dst_path, &backup_name) {
+            broken.push(BrokenBackup { name: backup_name, error: err.to_string() });
+        }
+    }
+    Ok(broken)
+}
+
+/// Rebuilds the `meta.json` of every broken backup in `dst_path`.
+pub fn repair_backups(dst_path: &String) -> Result<RepairReport, anyhow::Error> {
+    let mut report = RepairReport::default();
+    for broken in find_broken_backups(dst_path)? {
+        match repair_backup(dst_path, &broken.name) {
+            Ok(meta) => report.repaired.push(meta.name),
+            Err(err) => report.failed.push(BrokenBackup { name: broken.name, error: err.to_string() }),
+        }
+    }
+    Ok(report)
+}
+
+/// Writes a fresh `meta.json` for the backup called `backup_name` from the files it contains.
+/// The date is taken from the name if it contains one like `2024-05-01_18-30-00`, otherwise
+/// from the newest file. Pin, notes and tags of the old meta file are lost.
+///
+/// Deduplicated backups can't be repaired, their files are only known from the meta file.
+pub fn repair_backup(dst_path: &String, backup_name: &String) -> Result<SavegameMeta, anyhow::Error> {
+    check_not_newer(dst_path, backup_name)?;
+
+    let bak_pathbuf = PathBuf::from(dst_path).join(backup_name);
+    if bak_pathbuf.is_dir() {
+        return rebuild_meta(dst_path, backup_name, &bak_pathbuf, StorageMode::Folder, &bak_pathbuf, true);
+    }
+
+    match archive::find_archive(dst_path, backup_name) {
+        Some(archive_path) => {
+            let storage = archive::storage_for_path(&archive_path).unwrap_or_default();
+            let temp_pathbuf = std::env::temp_dir().join(format!("savegame_manager_repair_{}_{}", std::process::id(), backup_name));
+            // Extracted files get the current time as mtime from zip archives, so only the
+            // archive itself is trusted for the date
+            let result = archive::extract(&archive_path, &temp_pathbuf)
+                .and_then(|_| rebuild_meta(dst_path, backup_name, &temp_pathbuf, storage, &archive_path, false));
+            std::fs::remove_dir_all(&temp_pathbuf).unwrap_or_default();
+            result
+        },
+        None => Err(anyhow::anyhow!("Backup {} does not exist", backup_name)),
+    }
+}
+
+/// A meta file written by a newer version is not broken, just not understood. It must not be
+/// replaced with one that lacks whatever the newer version stored.
+fn check_not_newer(dst_path: &String, backup_name: &String) -> Result<(), anyhow::Error> {
+    let bak_pathbuf = PathBuf::from(dst_path).join(backup_name);
+    let content = if bak_pathbuf.is_dir() {
+        std::fs::read(bak_pathbuf.join("meta.json")).ok()
+    } else {
+        archive::find_archive(dst_path, backup_name).and_then(|path| archive::read_file(&path, "meta.json").ok().flatten())
+    };
+
+    let version = content
+        .and_then(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+        .and_then(|value| value.get("schema_version").and_then(|version| version.as_u64()));
+    match version {
+        Some(version) if version > schema::META_SCHEMA_VERSION as u64 => {
+            Err(anyhow::anyhow!("Backup {} was written by a newer version and is not broken", backup_name))
+        },
+        _ => Ok(()),
+    }
+}
+
+fn rebuild_meta(dst_path: &String, backup_name: &String, files_root: &Path, storage: StorageMode, backup_path: &Path, use_file_dates: bool) -> Result<SavegameMeta, anyhow::Error> {
+    let mut checksums: Vec<(String, String)> = vec![];
+    let mut newest_file: Option<SystemTime> = None;
+    for (relative_path, entry_path) in backup::list_files_recursive(files_root)? {
+        if backup::is_bookkeeping_file(&relative_path) {
+            continue;
+        }
+        if use_file_dates {
+            newest_file = newest_file.max(entry_path.metadata().and_then(|m| m.modified()).ok());
+        }
+        checksums.push((relative_path, fhc::file_blake3(&entry_path)?));
+    }
+
+    if checksums.is_empty() {
+        return Err(anyhow::anyhow!("Backup {} contains no files to rebuild its meta file from", backup_name));
+    }
+
+    let date = date_from_name(backup_name)
+        .or(newest_file.and_then(millis_from_system_time))
+        .or(backup_path.metadata().and_then(|m| m.modified()).ok().and_then(millis_from_system_time))
+        .unwrap_or_else(|| chrono::Local::now().timestamp_millis());
+
+    let meta = SavegameMeta { name: backup_name.clone(), date, kind: BackupKind::from_name(backup_name), checksums, storage, ..Default::default() };
+    backup::write_meta(dst_path, &meta)?;
+    println!("Rebuilt meta file of backup {}", backup_name);
+    Ok(meta)
+}
+
+/// Date in a backup name like `auto_2024-05-01_18-30-00` as unix milliseconds.
+fn date_from_name(backup_name: &str) -> Option<i64> {
+    (0..backup_name.len())
+        .filter_map(|i| backup_name.get(i..i + NAME_DATE_LENGTH))
+        .find_map(|part| chrono::NaiveDateTime::parse_from_str(part, NAME_DATE_FORMAT).ok())
+        .and_then(|date| date.and_local_timezone(chrono::Local).earliest())
+        .map(|date| date.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local_millis(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> i64 {
+        chrono::Local.with_ymd_and_hms(year, month, day, hour, min, sec).unwrap().timestamp_millis()
+    }
+
+    #[test]
+    fn parses_dates_of_generated_names() {
+        let expected = Some(local_millis(2024, 5, 1, 18, 30, 0));
+        assert_eq!(date_from_name("auto_2024-05-01_18-30-00"), expected);
+        assert_eq!(date_from_name("temp_2024-05-01_18-30-00"), expected);
+        assert_eq!(date_from_name("2024-05-01_18-30-00"), expected);
+        assert_eq!(date_from_name("Before the boss 2024-05-01_18-30-00 (copy)"), expected);
+    }
+
+    #[test]
+    fn ignores_names_without_a_date() {
+        assert_eq!(date_from_name("Before the boss"), None);
+        assert_eq!(date_from_name("auto_2024-13-01_18-30-00"), None);
+        assert_eq!(date_from_name("Spielstand für später"), None);
+        assert_eq!(date_from_name(""), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_file_date() {
+        let dst = test_dir("repair_date_fallback");
+        let dst_path = dst.to_string_lossy().to_string();
+        let backup_path = dst.join("Before the boss");
+        std::fs::create_dir(&backup_path).unwrap();
+        let modified = local_millis(2024, 5, 1, 18, 30, 0);
+        let file = std::fs::File::create(backup_path.join("slot1.sav")).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(modified as u64)).unwrap();
+        drop(file);
+
+        let meta = repair_backup(&dst_path, &String::from("Before the boss")).unwrap();
+        assert_eq!(meta.date, modified);
+        assert_eq!(meta.kind, BackupKind::Keep);
+        assert_eq!(meta.checksums.len(), 1);
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+use crate::*;
+use backup::SavegameMeta;
+
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+
+const MINUTE: i64 = 60 * 1_000;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// Grandfather-father-son style rules deciding which backups are kept when old backups are
+/// pruned. Temporary saves and pre-restore snapshots are not affected, they have rules of
+/// their own. Pinned backups are always kept.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Without a policy only temporary saves and autosaves beyond the maximum are pruned.
+    pub enabled: bool,
+    /// Every backup younger than this is kept.
+    pub keep_all_minutes: u32,
+    /// The newest backup of every hour is kept for this many hours.
+    pub hourly_hours: u32,
+    /// The newest backup of every day is kept for this many days.
+    pub daily_days: u32,
+    /// The newest backup of every week is kept for this many weeks.
+    pub weekly_weeks: u32,
+    /// The newest backup of every month is kept for this many months.
+    pub monthly_months: u32,
+    /// At most this many backups are kept. `0` means no limit.
+    pub max_count: u32,
+    /// The oldest backups are removed until all backups together take up less than this many
+    /// megabytes. `0` means no limit.
+    pub max_size_mb: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep_all_minutes: 60,
+            hourly_hours: 24,
+            daily_days: 30,
+            weekly_weeks: 0,
+            monthly_months: 0,
+            max_count: 0,
+            max_size_mb: 0,
+        }
+    }
+}
+
+/// Why a backup was pruned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    /// Temporary saves only live until the next backup.
+    TempSave,
+    /// There are more autosaves than the profile allows.
+    AutosaveLimit,
+    /// None of the rules of the retention policy covers the backup.
+    Retention,
+    /// There are more backups than the retention policy allows.
+    CountLimit,
+    /// All backups together are bigger than the retention policy allows.
+    SizeLimit,
+}
+
+impl std::fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::TempSave => "Temporary save",
+            Self::AutosaveLimit => "Exceeds the maximum number of autosaves",
+            Self::Retention => "Not covered by the retention policy",
+            Self::CountLimit => "Exceeds the maximum number of backups",
+            Self::SizeLimit => "Exceeds the maximum total size",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PrunedBackup {
+    pub name: String,
+    pub reason: PruneReason,
+}
+
+/// Backups removed by a prune, with the reason for each of them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PruneReport {
+    pub removed: Vec<PrunedBackup>,
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+impl std::fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for pruned in &self.removed {
+            writeln!(f, "{}: {}", pruned.name, pruned.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decides which of `backups` (newest first) have to be removed at the time `now` (unix
+/// milliseconds). `size_of` returns the size of a backup in bytes and is only called if the
+/// policy has a size limit.
+///
+/// Pinned backups are never removed and don't count towards any limit. The newest backup that
+/// is neither a temporary save nor a pre-restore snapshot is never removed either, so a prune
+/// right after a backup can't remove the backup itself.
+pub fn plan_prune(backups: &[SavegameMeta], max_autosaves: u16, policy: &RetentionPolicy, now: i64, size_of: impl Fn(&SavegameMeta) -> u64) -> Vec<PrunedBackup> {
+    let mut removed: Vec<PrunedBackup> = vec![];
+    let mut remove = |backup: &SavegameMeta, reason: PruneReason| removed.push(PrunedBackup { name: backup.name.clone(), reason });
+
+    let mut candidates: Vec<&SavegameMeta> = vec![];
+    let mut auto_count = 0;
+    for backup in backups {
+        if backup.pinned || backup.is_prerestore() {
+            continue;
+        }
+        if backup.is_temp() {
+            remove(backup, PruneReason::TempSave);
+            continue;
+        }
+        if backup.is_auto() {
+            auto_count += 1;
+            if auto_count > max_autosaves && !candidates.is_empty() {
+                remove(backup, PruneReason::AutosaveLimit);
+                continue;
+            }
+        }
+        candidates.push(backup);
+    }
+
+    if !policy.enabled {
+        return removed;
+    }
+
+    let rules: [(i64, &str); 4] = [
+        (policy.hourly_hours as i64 * HOUR, "%Y-%m-%d %H"),
+        (policy.daily_days as i64 * DAY, "%Y-%m-%d"),
+        (policy.weekly_weeks as i64 * 7 * DAY, "%G-%V"),
+        (policy.monthly_months as i64 * 31 * DAY, "%Y-%m"),
+    ];
+    let mut buckets: [HashSet<String>; 4] = Default::default();
+
+    let mut kept: Vec<&SavegameMeta> = vec![];
+    for (i, backup) in candidates.into_iter().enumerate() {
+        let age = now - backup.date;
+        let date = local_datetime_from_millis(backup.date);
+
+        // Every rule has to see every backup, so each bucket is claimed by its newest backup
+        let mut keep = i == 0 || age < policy.keep_all_minutes as i64 * MINUTE;
+        for (rule, bucket) in rules.iter().zip(buckets.iter_mut()) {
+            if age < rule.0 && bucket.insert(date.format(rule.1).to_string()) {
+                keep = true;
+            }
+        }
+
+        if keep {
+            kept.push(backup);
+        } else {
+            remove(backup, PruneReason::Retention);
+        }
+    }
+
+    let max_size = policy.max_size_mb * 1024 * 1024;
+    let mut total_size: u64 = 0;
+    for (i, backup) in kept.into_iter().enumerate() {
+        if i == 0 {
+            if max_size > 0 {
+                total_size += size_of(backup);
+            }
+            continue;
+        }
+
+        if policy.max_count > 0 && i >= policy.max_count as usize {
+            remove(backup, PruneReason::CountLimit);
+        } else if max_size > 0 {
+            total_size += size_of(backup);
+            if total_size > max_size {
+                remove(backup, PruneReason::SizeLimit);
+            }
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backup::BackupKind;
+    use chrono::TimeZone;
+
+    /// Unix milliseconds of the given local time in June 2024, which has no DST transitions.
+    fn june(day: u32, hour: u32) -> i64 {
+        chrono::Local.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap().timestamp_millis()
+    }
+
+    fn backup(name: &str, kind: BackupKind, date: i64) -> SavegameMeta {
+        SavegameMeta { name: name.to_owned(), kind, date, ..Default::default() }
+    }
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy { enabled: true, keep_all_minutes: 0, hourly_hours: 0, daily_days: 0, weekly_weeks: 0, monthly_months: 0, max_count: 0, max_size_mb: 0 }
+    }
+
+    fn removed_names(removed: &[PrunedBackup]) -> Vec<&str> {
+        removed.iter().map(|pruned| pruned.name.as_str()).collect()
+    }
+
+    #[test]
+    fn removes_temp_saves_and_autosaves_beyond_the_maximum() {
+        let now = june(20, 12);
+        let backups = vec![
+            backup("temp", BackupKind::Temp, june(20, 11)),
+            backup("auto_3", BackupKind::Auto, june(20, 10)),
+            backup("auto_2", BackupKind::Auto, june(20, 9)),
+            backup("auto_1", BackupKind::Auto, june(20, 8)),
+            backup("keep", BackupKind::Keep, june(20, 7)),
+        ];
+
+        let removed = plan_prune(&backups, 2, &RetentionPolicy::default(), now, |_| 0);
+        assert_eq!(removed_names(&removed), vec!["temp", "auto_1"]);
+        assert_eq!(removed[0].reason, PruneReason::TempSave);
+        assert_eq!(removed[1].reason, PruneReason::AutosaveLimit);
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_every_day() {
+        let now = june(20, 13);
+        let backups = vec![
+            backup("day0_noon", BackupKind::Keep, june(20, 12)),
+            backup("day0_morning", BackupKind::Keep, june(20, 8)),
+            backup("day1_noon", BackupKind::Keep, june(19, 12)),
+            backup("day1_morning", BackupKind::Keep, june(19, 8)),
+            backup("day2_noon", BackupKind::Keep, june(18, 12)),
+            backup("day5_noon", BackupKind::Keep, june(15, 12)),
+        ];
+
+        let removed = plan_prune(&backups, 10, &RetentionPolicy { daily_days: 3, ..policy() }, now, |_| 0);
+        assert_eq!(removed_names(&removed), vec!["day0_morning", "day1_morning", "day5_noon"]);
+        assert!(removed.iter().all(|pruned| pruned.reason == PruneReason::Retention));
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_every_week_and_month() {
+        // 2024-06-17 is a Monday
+        let now = june(20, 12);
+        let backups = vec![
+            backup("this_week", BackupKind::Keep, june(19, 12)),
+            backup("this_week_older", BackupKind::Keep, june(17, 12)),
+            backup("last_week", BackupKind::Keep, june(14, 12)),
+            backup("last_week_older", BackupKind::Keep, june(10, 12)),
+            backup("may", BackupKind::Keep, chrono::Local.with_ymd_and_hms(2024, 5, 20, 12, 0, 0).unwrap().timestamp_millis()),
+            backup("may_older", BackupKind::Keep, chrono::Local.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap().timestamp_millis()),
+        ];
+
+        let weekly = plan_prune(&backups, 10, &RetentionPolicy { weekly_weeks: 2, ..policy() }, now, |_| 0);
+        assert_eq!(removed_names(&weekly), vec!["this_week_older", "last_week_older", "may", "may_older"]);
+
+        let monthly = plan_prune(&backups, 10, &RetentionPolicy { monthly_months: 2, ..policy() }, now, |_| 0);
+        assert_eq!(removed_names(&monthly), vec!["this_week_older", "last_week", "last_week_older", "may_older"]);
+    }
+
+    #[test]
+    fn keeps_everything_younger_than_keep_all() {
+        let now = june(20, 12);
+        let backups = vec![
+            backup("newest", BackupKind::Keep, now - 10 * MINUTE),
+            backup("recent", BackupKind::Keep, now - 50 * MINUTE),
+            backup("old", BackupKind::Keep, now - 70 * MINUTE),
+        ];
+
+        let removed = plan_prune(&backups, 10, &RetentionPolicy { keep_all_minutes: 60, ..policy() }, now, |_| 0);
+        assert_eq!(removed_names(&removed), vec!["old"]);
+    }
+
+    #[test]
+    fn applies_count_and_size_limits() {
+        let now = june(20, 12);
+        let backups: Vec<SavegameMeta> = (0..4).map(|i| backup(&format!("backup_{}", i), BackupKind::Keep, now - i * MINUTE)).collect();
+        let all = RetentionPolicy { keep_all_minutes: 60, ..policy() };
+
+        let removed = plan_prune(&backups, 10, &RetentionPolicy { max_count: 2, ..all.clone() }, now, |_| 0);
+        assert_eq!(removed_names(&removed), vec!["backup_2", "backup_3"]);
+        assert!(removed.iter().all(|pruned| pruned.reason == PruneReason::CountLimit));
+
+        let ten_mb = |_: &SavegameMeta| 10 * 1024 * 1024;
+        let removed = plan_prune(&backups, 10, &RetentionPolicy { max_size_mb: 25, ..all }, now, ten_mb);
+        assert_eq!(removed_names(&removed), vec!["backup_2", "backup_3"]);
+        assert!(removed.iter().all(|pruned| pruned.reason == PruneReason::SizeLimit));
+    }
+
+    #[test]
+    fn never_removes_pinned_backups_or_the_newest_backup() {
+        let now = june(20, 12);
+        let mut pinned_temp = backup("pinned_temp", BackupKind::Temp, june(20, 11));
+        pinned_temp.pinned = true;
+        let mut pinned_old = backup("pinned_old", BackupKind::Keep, june(1, 12));
+        pinned_old.pinned = true;
+        let backups = vec![
+            pinned_temp,
+            backup("prerestore", BackupKind::Prerestore, june(20, 10)),
+            backup("newest", BackupKind::Auto, june(10, 12)),
+            pinned_old,
+            backup("oldest", BackupKind::Auto, june(1, 11)),
+        ];
+
+        let removed = plan_prune(&backups, 0, &RetentionPolicy { max_count: 1, max_size_mb: 1, ..policy() }, now, |_| 10 * 1024 * 1024);
+        assert_eq!(removed_names(&removed), vec!["oldest"]);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::*;
+use backup::{BackupKind, SavegameMeta};
+
+use serde_json::{Map, Value};
+
+/// Version of the `meta.json` format written by this build.
+pub const META_SCHEMA_VERSION: u32 = 2;
+
+/// Gets the name of the backup, which older versions used to encode information.
+type Migration = fn(&str, &mut Map<String, Value>) -> Result<(), anyhow::Error>;
+
+/// Migrations in order. The one at index `n` upgrades schema version `n` to `n + 1`.
+const MIGRATIONS: [Migration; META_SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+];
+
+/// Parses the content of a `meta.json` of any known schema version and upgrades it to
+/// [`META_SCHEMA_VERSION`]. The version it was stored with is kept in
+/// [`SavegameMeta::stored_schema_version`].
+pub fn parse_meta(backup_name: &str, content: &[u8]) -> Result<SavegameMeta, anyhow::Error> {
+    let mut value: Value = serde_json::from_slice(content)?;
+    let meta = match value.as_object_mut() {
+        Some(meta) => meta,
+        None => return Err(anyhow::anyhow!("Backup meta file does not contain an object")),
+    };
+
+    let stored_version = match meta.get("schema_version") {
+        Some(version) => match version.as_u64() {
+            Some(version) => version as u32,
+            None => return Err(anyhow::anyhow!("Backup meta file has an invalid schema version: {}", version)),
+        },
+        None => 0,
+    };
+    if stored_version > META_SCHEMA_VERSION {
+        return Err(anyhow::anyhow!("Backup meta file was written by a newer version (schema {}, supported up to {})", stored_version, META_SCHEMA_VERSION));
+    }
+
+    for migration in &MIGRATIONS[stored_version as usize..] {
+        migration(backup_name, meta)?;
+    }
+
+    let mut meta: SavegameMeta = serde_json::from_value(value)?;
+    meta.stored_schema_version = stored_version;
+    Ok(meta)
+}
+
+/// Version 0 is every `meta.json` written before the schema was versioned. Storage mode, pin,
+/// notes and tags were added over time without a version, so missing ones get the values
+/// older builds assumed.
+fn migrate_v0_to_v1(_backup_name: &str, meta: &mut Map<String, Value>) -> Result<(), anyhow::Error> {
+    if !meta.contains_key("date") || !meta.contains_key("checksums") {
+        return Err(anyhow::anyhow!("Backup meta file has no date or checksums"));
+    }
+
+    meta.entry("storage").or_insert(serde_json::to_value(storage::StorageMode::Folder)?);
+    meta.entry("pinned").or_insert(Value::Bool(false));
+    meta.entry("notes").or_insert(Value::String(String::new()));
+    meta.entry("tags").or_insert(Value::Array(vec![]));
+    meta.insert(String::from("schema_version"), Value::from(1));
+    Ok(())
+}
+
+/// Version 2 stores the kind of the backup. Before, it was only known from the name prefix.
+fn migrate_v1_to_v2(backup_name: &str, meta: &mut Map<String, Value>) -> Result<(), anyhow::Error> {
+    meta.entry("kind").or_insert(serde_json::to_value(BackupKind::from_name(backup_name))?);
+    meta.insert(String::from("schema_version"), Value::from(2));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::StorageMode;
+
+    #[test]
+    fn migrates_unversioned_meta() {
+        let content = br#"{"date": 1718000000000, "checksums": [["slot1.sav", "abc"]]}"#;
+        let meta = parse_meta("auto_2024-06-10_08-13-20", content).unwrap();
+
+        assert_eq!(meta.stored_schema_version, 0);
+        assert_eq!(meta.schema_version, META_SCHEMA_VERSION);
+        assert_eq!(meta.date, 1718000000000);
+        assert_eq!(meta.checksums, vec![(String::from("slot1.sav"), String::from("abc"))]);
+        assert_eq!(meta.storage, StorageMode::Folder);
+        assert!(!meta.pinned);
+        assert_eq!(meta.notes, "");
+        assert!(meta.tags.is_empty());
+        assert_eq!(meta.kind, BackupKind::Auto);
+    }
+
+    #[test]
+    fn migrates_v1_meta() {
+        let content = br#"{"schema_version": 1, "date": 1718000000000, "checksums": [], "storage": "Deduplicated", "pinned": true, "notes": "Before the boss", "tags": ["boss"]}"#;
+        let meta = parse_meta("temp_2024-06-10_08-13-20", content).unwrap();
+
+        assert_eq!(meta.stored_schema_version, 1);
+        assert_eq!(meta.schema_version, META_SCHEMA_VERSION);
+        assert_eq!(meta.storage, StorageMode::Deduplicated);
+        assert!(meta.pinned);
+        assert_eq!(meta.notes, "Before the boss");
+        assert_eq!(meta.tags, vec![String::from("boss")]);
+        assert_eq!(meta.kind, BackupKind::Temp);
+    }
+
+    #[test]
+    fn keeps_the_stored_kind() {
+        let content = br#"{"schema_version": 2, "date": 0, "checksums": [], "storage": "Folder", "pinned": false, "notes": "", "tags": [], "kind": "manual"}"#;
+        let meta = parse_meta("auto_renamed", content).unwrap();
+
+        assert_eq!(meta.stored_schema_version, 2);
+        assert_eq!(meta.kind, BackupKind::Manual);
+    }
+
+    #[test]
+    fn rejects_unknown_or_incomplete_meta() {
+        assert!(parse_meta("backup", br#"{"schema_version": 99, "date": 0, "checksums": []}"#).is_err());
+        assert!(parse_meta("backup", br#"{"checksums": []}"#).is_err());
+        assert!(parse_meta("backup", br#"[]"#).is_err());
+    }
+}
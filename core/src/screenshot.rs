@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+#[cfg(feature = "screenshots")]
+use screenshots::{display_info::DisplayInfo, image::ImageFormat, Screen};
+
+#[derive(Clone)]
+pub enum ScreenshotState {
+    Idle,
+    Busy,
+    Finished,
+    Error,
+}
+
+#[cfg(feature = "screenshots")]
+fn take_screenshot(path: &PathBuf) -> Result<(), anyhow::Error> {
+    let screens = DisplayInfo::all()?;
+
+    let mut took_screenshot = false;
+    for screen in screens {
+        if screen.is_primary {
+            Screen::new(&screen).capture()?.save_with_format(path, ImageFormat::Jpeg)?;
+            took_screenshot = true;
+            break;
+        }
+    }
+
+    if !took_screenshot {
+        return Err(anyhow::anyhow!("No primary display found"));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "screenshots"))]
+fn take_screenshot(_path: &PathBuf) -> Result<(), anyhow::Error> {
+    Err(anyhow::anyhow!("Screenshots are not supported by this build"))
+}
+
+/// Whether this build can take screenshots. Builds without the `screenshots` feature can't.
+pub fn is_supported() -> bool {
+    cfg!(feature = "screenshots")
+}
+
+/// Takes a screenshot of the primary display and stores it as JPEG at `path`, where the
+/// next backup picks it up.
+pub fn create_screenshot(path: &PathBuf) -> Result<(), anyhow::Error> {
+    match take_screenshot(path) {
+        Ok(_) => {
+            println!("Screenshot saved");
+            Ok(())
+        },
+        Err(err) => {
+            println!("Could not create screenshot: {:?}", err);
+            Err(anyhow::anyhow!("Could not create screenshot"))
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::*;
+use filter::FileFilter;
+use hashcache::HashCache;
+
+use std::path::PathBuf;
+
+/// Relative path, size and checksum of every file, as seen by one poll.
+type Snapshot = Vec<(String, u64, String)>;
+
+/// Tells when a game finished writing its savegame, by checking that the sizes and checksums of
+/// the files stayed the same for a number of polls in a row.
+///
+/// Games that write a save in several passes pause between them, so a quiet period alone does
+/// not guarantee the files are complete. Files that can not be read, e.g. because the game
+/// holds an exclusive lock, count as changed.
+#[derive(Default)]
+pub struct StabilityDetector {
+    last: Option<Snapshot>,
+    unchanged_polls: u16,
+}
+
+impl StabilityDetector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Forgets everything seen so far, e.g. because new file events arrived.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.unchanged_polls = 0;
+    }
+
+    /// Takes a snapshot of the files in `src_path` matching `filter` and returns whether it was
+    /// the same for the last `required_polls` polls. With `0` the files count as stable right away.
+    /// Checksums of files that did not change are taken from `cache`.
+    pub fn poll(&mut self, src_path: &String, filter: &FileFilter, cache: &mut HashCache, required_polls: u16) -> bool {
+        match snapshot(src_path, filter, cache) {
+            Ok(current) => {
+                if self.last.as_ref() == Some(&current) {
+                    self.unchanged_polls = self.unchanged_polls.saturating_add(1);
+                } else {
+                    self.unchanged_polls = 0;
+                    self.last = Some(current);
+                }
+                self.unchanged_polls >= required_polls
+            },
+            Err(err) => {
+                println!("Files of {} are not readable yet: {:?}", src_path, err);
+                self.reset();
+                false
+            }
+        }
+    }
+}
+
+fn snapshot(src_path: &String, filter: &FileFilter, cache: &mut HashCache) -> Result<Snapshot, anyhow::Error> {
+    let src_pathbuf = PathBuf::from(src_path);
+    let files = backup::list_source_files(&src_pathbuf, filter)?;
+    let checksums = cache.hash_files(&src_pathbuf, &files)?;
+
+    let mut snapshot: Snapshot = vec![];
+    for ((relative_path, path), (_, checksum)) in files.iter().zip(checksums) {
+        snapshot.push((relative_path.clone(), path.metadata()?.len(), checksum));
+    }
+    Ok(snapshot)
+}
+
+/// Runs `operation` and runs it again after each of the pauses in `delays_millis` as long as
+/// it fails with an error `is_in_use` accepts, e.g. because the game holds a file open.
+pub fn retry_while_in_use<T>(delays_millis: &[u64], is_in_use: impl Fn(&anyhow::Error) -> bool, mut operation: impl FnMut() -> Result<T, anyhow::Error>) -> Result<T, anyhow::Error> {
+    let mut result = operation();
+    for delay in delays_millis {
+        match &result {
+            Err(err) if is_in_use(err) => {
+                println!("Savegame is in use, retrying in {} ms: {}", delay, err);
+                std::thread::sleep(std::time::Duration::from_millis(*delay));
+                result = operation();
+            },
+            _ => break,
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(detector: &mut StabilityDetector, src: &std::path::Path, cache: &mut HashCache, required_polls: u16) -> bool {
+        detector.poll(&src.to_string_lossy().to_string(), &FileFilter::default(), cache, required_polls)
+    }
+
+    #[test]
+    fn stable_after_required_unchanged_polls() {
+        let src = test_dir("stability_unchanged");
+        std::fs::write(src.join("slot1.sav"), "first").unwrap();
+        let mut cache = HashCache::default();
+        let mut detector = StabilityDetector::new();
+
+        assert!(!poll(&mut detector, &src, &mut cache, 2));
+        assert!(!poll(&mut detector, &src, &mut cache, 2));
+        assert!(poll(&mut detector, &src, &mut cache, 2));
+
+        detector.reset();
+        assert!(!poll(&mut detector, &src, &mut cache, 1));
+        assert!(poll(&mut detector, &src, &mut cache, 0));
+        std::fs::remove_dir_all(src).unwrap();
+    }
+
+    #[test]
+    fn changes_start_counting_again() {
+        let src = test_dir("stability_changed");
+        std::fs::write(src.join("slot1.sav"), "first").unwrap();
+        let mut cache = HashCache::default();
+        let mut detector = StabilityDetector::new();
+
+        assert!(!poll(&mut detector, &src, &mut cache, 1));
+        std::fs::write(src.join("slot1.sav"), "second pass").unwrap();
+        assert!(!poll(&mut detector, &src, &mut cache, 1));
+        std::fs::write(src.join("slot2.sav"), "new file").unwrap();
+        assert!(!poll(&mut detector, &src, &mut cache, 1));
+        assert!(poll(&mut detector, &src, &mut cache, 1));
+        std::fs::remove_dir_all(src).unwrap();
+    }
+
+    #[test]
+    fn unreadable_files_are_not_stable() {
+        let src = test_dir("stability_unreadable");
+        let mut cache = HashCache::default();
+        let mut detector = StabilityDetector::new();
+
+        assert!(!poll(&mut detector, &src.join("missing"), &mut cache, 0));
+        std::fs::remove_dir_all(src).unwrap();
+    }
+
+    fn is_not_found(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<std::io::Error>().is_some_and(|io_err| io_err.kind() == std::io::ErrorKind::NotFound)
+    }
+
+    #[test]
+    fn retries_until_the_file_can_be_read() {
+        let src = test_dir("stability_retry");
+        let file = src.join("slot1.sav");
+        let mut attempts = 0;
+        let result = retry_while_in_use(&[0, 0, 0], is_not_found, || {
+            attempts += 1;
+            if attempts == 3 {
+                std::fs::write(&file, "saved")?;
+            }
+            Ok(std::fs::read_to_string(&file)?)
+        });
+
+        assert_eq!(result.unwrap(), "saved");
+        assert_eq!(attempts, 3);
+        std::fs::remove_dir_all(src).unwrap();
+    }
+
+    #[test]
+    fn gives_up_after_the_last_delay_or_on_other_errors() {
+        let src = test_dir("stability_give_up");
+        let file = src.join("slot1.sav");
+
+        let mut attempts = 0;
+        let result = retry_while_in_use(&[0, 0], is_not_found, || {
+            attempts += 1;
+            Ok(std::fs::read_to_string(&file)?)
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: Result<(), anyhow::Error> = retry_while_in_use(&[0, 0], is_not_found, || {
+            attempts += 1;
+            Err(anyhow::anyhow!("Disk full"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        std::fs::remove_dir_all(src).unwrap();
+    }
+}
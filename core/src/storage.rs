@@ -0,0 +1,159 @@
+use crate::*;
+use backup::SavegameMeta;
+
+use std::{collections::HashSet, fs::File, path::PathBuf, time::{Duration, SystemTime}};
+use serde::{Serialize, Deserialize};
+
+/// Hidden folder below the backup folder that holds the deduplicated file contents.
+pub const STORE_FOLDER: &str = ".store";
+
+/// Objects younger than this are never garbage collected, as they may belong to a backup that
+/// is still being written and not referenced by any `meta.json` yet.
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// How the files of a backup are stored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum StorageMode {
+    /// Every backup is a folder with a full copy of every file.
+    #[default]
+    Folder,
+    /// File contents are stored once under their checksum in [`STORE_FOLDER`] and shared
+    /// between backups. The backup folder only contains `meta.json` and the screenshot.
+    Deduplicated,
+    /// Every backup is a single zip archive next to the backup folders.
+    Zip,
+    /// Every backup is a single zstd compressed tar archive next to the backup folders.
+    TarZst,
+}
+
+impl std::fmt::Display for StorageMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Folder => "Full copies",
+            Self::Deduplicated => "Deduplicated",
+            Self::Zip => "Zip archives",
+            Self::TarZst => "tar.zst archives",
+        })
+    }
+}
+
+/// Path of the stored content with the given checksum.
+pub fn object_path(dst_path: &String, checksum: &String) -> PathBuf {
+    let prefix = if checksum.len() >= 2 { &checksum[..2] } else { "00" };
+    PathBuf::from(dst_path).join(STORE_FOLDER).join(prefix).join(checksum)
+}
+
+/// Path the content of `file` of `backup` can be read from, depending on how it is stored.
+/// Archives have to be extracted with [`archive::extract`] first, their path points into the
+/// folder the archive was extracted to.
+pub fn stored_file_path(dst_path: &String, backup: &SavegameMeta, file: &str, checksum: &String) -> PathBuf {
+    match backup.storage {
+        StorageMode::Folder | StorageMode::Zip | StorageMode::TarZst => backup::relative_to_path(&PathBuf::from(dst_path).join(&backup.name), file),
+        StorageMode::Deduplicated => object_path(dst_path, checksum),
+    }
+}
+
+/// Adds the content of `file` to the store unless content with the same checksum is already
+/// stored. The copy is verified before it is moved into place.
+pub fn store_file(dst_path: &String, file: &PathBuf, checksum: &String) -> Result<(), anyhow::Error> {
+    let target = object_path(dst_path, checksum);
+    if target.is_file() {
+        // Mark the object as in use, so a concurrent garbage collection keeps it
+        File::options().append(true).open(&target)?.set_modified(SystemTime::now())?;
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = target.with_extension("tmp");
+    std::fs::copy(file, &temp)?;
+    if fhc::file_blake3(&temp)? != *checksum {
+        std::fs::remove_file(&temp).unwrap_or_default();
+        return Err(anyhow::anyhow!("{} changed while it was backed up", file.display()));
+    }
+    std::fs::rename(&temp, &target)?;
+
+    Ok(())
+}
+
+/// Removes every stored object that is not referenced by any backup anymore. Returns the
+/// checksums of the removed objects.
+///
+/// Backups whose `meta.json` can not be read are skipped with a warning, so a single broken
+/// or hand-copied folder doesn't keep the store from ever being cleaned up.
+pub fn collect_garbage(dst_path: &String) -> Result<Vec<String>, anyhow::Error> {
+    let store_path = PathBuf::from(dst_path).join(STORE_FOLDER);
+    if !store_path.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut referenced: HashSet<String> = HashSet::new();
+    for entry in std::fs::read_dir(dst_path)? {
+        let entry_path = entry?.path();
+        let name = String::from(entry_path.file_name().unwrap_or_default().to_str().unwrap_or_default());
+        if name.starts_with(".") || !entry_path.is_dir() {
+            continue;
+        }
+
+        match backup::get_meta_for_backup(dst_path, &name) {
+            Ok(meta) => {
+                if meta.storage == StorageMode::Deduplicated {
+                    referenced.extend(meta.checksums.into_iter().map(|(_, checksum)| checksum));
+                }
+            },
+            Err(err) => println!("Backup {} can not be read, ignoring it while collecting garbage: {:?}", name, err),
+        }
+    }
+
+    let mut removed: Vec<String> = vec![];
+    let now = SystemTime::now();
+    for (_, object) in backup::list_files_recursive(&store_path)? {
+        let checksum = String::from(object.file_name().unwrap_or_default().to_str().unwrap_or_default());
+        if referenced.contains(&checksum) {
+            continue;
+        }
+
+        let age = object.metadata().and_then(|m| m.modified()).ok().and_then(|modified| now.duration_since(modified).ok());
+        if age.map(|age| age > GC_GRACE_PERIOD).unwrap_or(false) && std::fs::remove_file(&object).is_ok() {
+            removed.push(checksum);
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stores `content` and backdates the object past the grace period.
+    fn store_old(dst: &std::path::Path, content: &str) -> String {
+        let file = dst.join("content.tmp");
+        std::fs::write(&file, content).unwrap();
+        let checksum = fhc::file_blake3(&file).unwrap();
+        let dst_path = dst.to_string_lossy().to_string();
+        store_file(&dst_path, &file, &checksum).unwrap();
+        std::fs::remove_file(file).unwrap();
+        File::options().append(true).open(object_path(&dst_path, &checksum)).unwrap().set_modified(SystemTime::now() - GC_GRACE_PERIOD - Duration::from_secs(60)).unwrap();
+        checksum
+    }
+
+    #[test]
+    fn removes_unreferenced_objects_and_skips_unreadable_backups() {
+        let dst = test_dir("storage_gc");
+        let dst_path = dst.to_string_lossy().to_string();
+        let used = store_old(&dst, "used");
+        let unused = store_old(&dst, "unused");
+
+        std::fs::create_dir(dst.join("backup")).unwrap();
+        let meta = SavegameMeta { name: String::from("backup"), checksums: vec![(String::from("slot1.sav"), used.clone())], storage: StorageMode::Deduplicated, ..Default::default() };
+        backup::write_meta(&dst_path, &meta).unwrap();
+        std::fs::create_dir(dst.join("copied_by_hand")).unwrap();
+
+        assert_eq!(collect_garbage(&dst_path).unwrap(), vec![unused.clone()]);
+        assert!(object_path(&dst_path, &used).is_file());
+        assert!(!object_path(&dst_path, &unused).exists());
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+}
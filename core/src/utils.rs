@@ -0,0 +1,54 @@
+use std::sync::RwLock;
+use chrono::TimeZone;
+
+pub fn read_rwlock_or<T: Clone>(lock: &RwLock<T>, default: T) -> T {
+    match lock.read() {
+        Ok(value) => value.clone(),
+        Err(_) => default,
+    }
+}
+
+pub fn write_to_rwlock<T>(lock: &RwLock<T>, value: T) {
+    match lock.write() {
+        Ok(mut lock) => *lock = value,
+        Err(err) => println!("Error writing to RwLock: {:?}", err),
+    }
+}
+
+pub fn local_datetime_from_millis(millis: i64) -> chrono::DateTime<chrono::Local> {
+    match chrono::Local.timestamp_millis_opt(millis) {
+        chrono::offset::LocalResult::Single(ts) => ts,
+        chrono::offset::LocalResult::Ambiguous(ts, _) => ts,
+        _ => chrono::Local::now(),
+    }
+}
+
+/// Unix milliseconds of a file time, `None` if it lies before 1970.
+pub fn millis_from_system_time(time: std::time::SystemTime) -> Option<i64> {
+    time.duration_since(std::time::SystemTime::UNIX_EPOCH).ok().map(|duration| duration.as_millis() as i64)
+}
+
+/// Whether `err` was caused by a file another process holds open without sharing it, which
+/// only happens on Windows (`ERROR_SHARING_VIOLATION` and `ERROR_LOCK_VIOLATION`).
+pub fn is_sharing_violation(err: &anyhow::Error) -> bool {
+    cfg!(windows) && err.chain().any(|cause| {
+        cause.downcast_ref::<std::io::Error>().is_some_and(|io_err| matches!(io_err.raw_os_error(), Some(32) | Some(33)))
+    })
+}
+
+pub fn interval_duration(interval: u16, interval_unit: &crate::profile::ProfileIntervalUnit) -> i64 {
+    match interval_unit {
+        crate::profile::ProfileIntervalUnit::Seconds => interval as i64 * 1_000,
+        crate::profile::ProfileIntervalUnit::Minutes => interval as i64 * 60 * 1_000,
+        crate::profile::ProfileIntervalUnit::Hours => interval as i64 * 60 * 60 * 1_000,
+    }
+}
+
+/// Empty folder below the system's temp folder for a test, unique per test and process.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join("savegame_manager_tests").join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
\ No newline at end of file
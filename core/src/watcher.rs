@@ -0,0 +1,580 @@
+use crate::*;
+use filter::FileFilter;
+
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex, RwLock}, time::Duration};
+use notify::{PollWatcher, RecommendedWatcher, Watcher};
+use serde::{Deserialize, Serialize};
+
+/// How often the native backend is checked for missed events, in milliseconds.
+const NATIVE_CHECK_INTERVAL: i64 = 10_000;
+
+/// How a [`SourceWatcher`] learns about changed files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum WatcherBackend {
+    /// Events of the operating system. Falls back to polling if they stop arriving.
+    #[default]
+    Native,
+    /// Scans the folder in an interval. Works on network shares and FUSE file systems.
+    Polling,
+    /// Native events and polling at the same time.
+    Hybrid,
+}
+
+impl std::fmt::Display for WatcherBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Native => "Native",
+            Self::Polling => "Polling",
+            Self::Hybrid => "Native and polling",
+        })
+    }
+}
+
+/// How well a [`SourceWatcher`] is doing, to be shown to the user.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherHealth {
+    /// Nothing is watched, e.g. because no folders are set.
+    Stopped,
+    /// Changes are noticed with the configured backend.
+    Watching,
+    /// Native events stopped arriving, so the folder is polled.
+    FellBackToPolling,
+    /// The source folder does not exist at the moment. The watcher re-attaches once it is back.
+    SourceMissing,
+}
+
+impl std::fmt::Display for WatcherHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stopped => "Not watching",
+            Self::Watching => "Watching",
+            Self::FellBackToPolling => "Polling, native events stopped",
+            Self::SourceMissing => "Waiting for the savegame folder",
+        })
+    }
+}
+
+/// State shared between a [`SourceWatcher`] and the thread notify delivers its events on.
+#[derive(Default)]
+pub struct WatcherState {
+    pub has_changes: RwLock<bool>,
+    pub latest_change: RwLock<i64>,
+    pub paused: RwLock<bool>,
+    /// Time of the latest event of the native backend, whether it was ignored or not.
+    pub latest_native_event: RwLock<i64>,
+    /// The native backend reported an error.
+    pub native_failed: RwLock<bool>,
+    /// The source folder itself was removed, renamed or created, so the watches on it are stale.
+    pub source_replaced: RwLock<bool>,
+}
+
+#[derive(Clone)]
+struct SavegameSourceWatchEventHandler {
+    state: Arc<WatcherState>,
+    root: PathBuf,
+    /// Native backends may report absolute paths for a relative `root`.
+    canonical_root: PathBuf,
+    filter: FileFilter,
+    native: bool,
+}
+
+impl SavegameSourceWatchEventHandler {
+    fn is_watched_file(&self, path: &Path) -> bool {
+        path.is_file() && (self.filter.matches_path(&self.root, path) || self.filter.matches_path(&self.canonical_root, path))
+    }
+
+    fn is_root(&self, path: &PathBuf) -> bool {
+        *path == self.root || *path == self.canonical_root
+    }
+}
+
+impl notify::EventHandler for SavegameSourceWatchEventHandler {
+    fn handle_event(&mut self, event: notify::Result<notify::Event>) {
+        let paused = read_rwlock_or(&self.state.paused, false);
+        if paused {
+            return;
+        }
+
+        println!("File event: {:?}", event);
+        if self.native {
+            match &event {
+                Ok(_) => write_to_rwlock(&self.state.latest_native_event, chrono::Utc::now().timestamp_millis()),
+                Err(_) => write_to_rwlock(&self.state.native_failed, true),
+            }
+        }
+
+        if let Ok(ev) = event {
+            let root_moved = ev.kind.is_remove() || matches!(ev.kind, notify::EventKind::Modify(notify::event::ModifyKind::Name(_)));
+            if root_moved && ev.paths.iter().any(|path| self.is_root(path)) {
+                write_to_rwlock(&self.state.source_replaced, true);
+            }
+
+            // Ignore folders and files the profile's patterns exclude
+            let mut only_ignored = true;
+            for path in ev.paths {
+                if self.is_watched_file(&path) {
+                    only_ignored = false;
+                    break;
+                }
+            }
+            if only_ignored {
+                return;
+            }
+
+            write_to_rwlock(&self.state.has_changes, true);
+            write_to_rwlock(&self.state.latest_change, chrono::Utc::now().timestamp_millis());
+        }
+    }
+}
+
+/// Watches the parent of the source folder, to notice the source folder being deleted,
+/// recreated or renamed.
+struct SourceParentEventHandler {
+    state: Arc<WatcherState>,
+    root: PathBuf,
+    canonical_root: PathBuf,
+}
+
+impl notify::EventHandler for SourceParentEventHandler {
+    fn handle_event(&mut self, event: notify::Result<notify::Event>) {
+        if let Ok(ev) = event {
+            // Only creating, removing or renaming the source folder itself counts. Plain modifications
+            // are ignored, since writes inside the folder show up as modifications of it on some platforms
+            let replaced = ev.kind.is_create() || ev.kind.is_remove() || matches!(ev.kind, notify::EventKind::Modify(notify::event::ModifyKind::Name(_)));
+            if replaced && ev.paths.iter().any(|path| *path == self.root || *path == self.canonical_root) {
+                write_to_rwlock(&self.state.source_replaced, true);
+            }
+        }
+    }
+}
+
+/// Sizes and modification times of the watched files, taken when the native backend is checked.
+#[derive(Default)]
+struct NativeCheck {
+    checked_at: i64,
+    files: Option<Vec<(String, u64, Option<i64>)>>,
+}
+
+/// Watches a savegame folder and records when its files changed.
+///
+/// If the folder is deleted or replaced, e.g. by a game that recreates it on every save or by
+/// a cloud sync client, [`SourceWatcher::check_source`] notices it and re-attaches the watches
+/// once the folder exists again.
+#[derive(Default)]
+pub struct SourceWatcher {
+    path: Option<PathBuf>,
+    filter: FileFilter,
+    backend: WatcherBackend,
+    poll_interval: Duration,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    poller: Mutex<Option<PollWatcher>>,
+    parent_watcher: Mutex<Option<RecommendedWatcher>>,
+    fell_back: RwLock<bool>,
+    source_missing: RwLock<bool>,
+    native_check: Mutex<NativeCheck>,
+    state: Arc<WatcherState>,
+}
+
+impl SourceWatcher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// (Re)starts watching `source_path` with the given `backend`. Only changes to files
+    /// matching `filter` are recorded. Nothing is watched if either path is not an existing
+    /// directory, which is not considered an error. Returns `false` if the watcher could not be created.
+    pub fn start(&mut self, source_path: &str, dest_path: &str, filter: FileFilter, backend: WatcherBackend, poll_interval: Duration) -> bool {
+        let src_path = Path::new(source_path);
+        let dst_path = Path::new(dest_path);
+
+        self.stop();
+
+        if !source_path.is_empty() && !dest_path.is_empty() && src_path.exists() && dst_path.exists() && src_path.is_dir() && dst_path.is_dir() {
+            self.path = Some(src_path.to_owned());
+            self.filter = filter;
+            self.backend = backend;
+            self.poll_interval = poll_interval;
+
+            if self.attach() {
+                self.start_parent_watcher();
+                true
+            } else {
+                self.stop();
+                false
+            }
+        } else {
+            true
+        }
+    }
+
+    /// Starts the watches on the source folder according to the backend.
+    fn attach(&self) -> bool {
+        write_to_rwlock(&self.state.native_failed, false);
+        write_to_rwlock(&self.state.source_replaced, false);
+        *self.native_check.lock().unwrap() = NativeCheck::default();
+
+        let native_started = match self.backend {
+            WatcherBackend::Native | WatcherBackend::Hybrid => self.start_native(),
+            WatcherBackend::Polling => true,
+        };
+        let polling_started = match self.backend {
+            WatcherBackend::Polling | WatcherBackend::Hybrid => self.start_polling(),
+            WatcherBackend::Native if read_rwlock_or(&self.fell_back, false) => self.start_polling(),
+            WatcherBackend::Native => true,
+        };
+        native_started && polling_started
+    }
+
+    /// Drops the watches on the source folder, but not the one on its parent.
+    fn detach(&self) {
+        if let Some(current_path) = self.path.as_ref() {
+            if let Some(rec_watch) = self.watcher.lock().unwrap().as_mut() {
+                rec_watch.unwatch(current_path).unwrap_or_default();
+            }
+            if let Some(poll_watch) = self.poller.lock().unwrap().as_mut() {
+                poll_watch.unwatch(current_path).unwrap_or_default();
+            }
+        }
+        *self.watcher.lock().unwrap() = None;
+        *self.poller.lock().unwrap() = None;
+    }
+
+    fn event_handler(&self, native: bool) -> Option<SavegameSourceWatchEventHandler> {
+        let root = self.path.clone()?;
+        let canonical_root = std::fs::canonicalize(&root).unwrap_or(root.clone());
+        Some(SavegameSourceWatchEventHandler { state: self.state.clone(), root, canonical_root, filter: self.filter.clone(), native })
+    }
+
+    /// A folder that can't be watched natively, e.g. because the system ran out of watches,
+    /// counts as failed backend, so [`SourceWatcher::check_native_events`] falls back to polling.
+    fn start_native(&self) -> bool {
+        let (Some(path), Some(handler)) = (self.path.clone(), self.event_handler(true)) else {
+            return false;
+        };
+        if let Ok(mut rec_watch) = notify::recommended_watcher(handler) {
+            if let Err(err) = rec_watch.watch(&path, notify::RecursiveMode::Recursive) {
+                println!("Error watching {} natively: {:?}", path.display(), err);
+                write_to_rwlock(&self.state.native_failed, true);
+            }
+            *self.watcher.lock().unwrap() = Some(rec_watch);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn start_polling(&self) -> bool {
+        let (Some(path), Some(handler)) = (self.path.clone(), self.event_handler(false)) else {
+            return false;
+        };
+        match PollWatcher::new(handler, notify::Config::default().with_poll_interval(self.poll_interval)) {
+            Ok(mut poll_watch) => {
+                poll_watch.watch(&path, notify::RecursiveMode::Recursive).unwrap_or_default();
+                *self.poller.lock().unwrap() = Some(poll_watch);
+                true
+            },
+            Err(err) => {
+                println!("Error starting polling watcher: {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// Watches the parent folder non-recursively. Failing to do so is not an error, the source
+    /// folder is still checked on every [`SourceWatcher::check_source`].
+    fn start_parent_watcher(&self) {
+        let Some(root) = self.path.clone() else {
+            return;
+        };
+        let canonical_root = std::fs::canonicalize(&root).unwrap_or(root.clone());
+        let Some(parent) = canonical_root.parent().map(|p| p.to_owned()) else {
+            return;
+        };
+
+        let handler = SourceParentEventHandler { state: self.state.clone(), root, canonical_root };
+        if let Ok(mut parent_watch) = notify::recommended_watcher(handler) {
+            if parent_watch.watch(&parent, notify::RecursiveMode::NonRecursive).is_ok() {
+                *self.parent_watcher.lock().unwrap() = Some(parent_watch);
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.detach();
+        *self.parent_watcher.lock().unwrap() = None;
+        write_to_rwlock(&self.fell_back, false);
+        write_to_rwlock(&self.source_missing, false);
+        self.path = None;
+    }
+
+    /// The backend the watcher was started with.
+    pub fn backend(&self) -> WatcherBackend {
+        self.backend
+    }
+
+    /// Whether the folder is scanned in an interval, either because the backend says so or
+    /// because the native backend missed events.
+    pub fn is_polling(&self) -> bool {
+        self.backend != WatcherBackend::Native || read_rwlock_or(&self.fell_back, false)
+    }
+
+    pub fn health(&self) -> WatcherHealth {
+        if self.path.is_none() {
+            WatcherHealth::Stopped
+        } else if read_rwlock_or(&self.source_missing, false) {
+            WatcherHealth::SourceMissing
+        } else if self.backend == WatcherBackend::Native && read_rwlock_or(&self.fell_back, false) {
+            WatcherHealth::FellBackToPolling
+        } else {
+            WatcherHealth::Watching
+        }
+    }
+
+    /// Detaches the watches when the source folder disappeared and re-attaches them once it
+    /// exists again, or right away if it was replaced between two calls. A folder that comes
+    /// back counts as change. Returns `true` if the [`WatcherHealth`] changed.
+    pub fn check_source(&self) -> bool {
+        let Some(path) = self.path.as_ref() else {
+            return false;
+        };
+        if self.is_paused() {
+            return false;
+        }
+
+        let missing = read_rwlock_or(&self.source_missing, false);
+        let replaced = read_rwlock_or(&self.state.source_replaced, false);
+        let exists = path.is_dir();
+
+        if !exists && !missing {
+            println!("Source folder {} disappeared, waiting for it to come back", path.display());
+            self.detach();
+            write_to_rwlock(&self.source_missing, true);
+            write_to_rwlock(&self.state.source_replaced, false);
+            true
+        } else if exists && (missing || replaced) {
+            println!("Source folder {} is back, watching it again", path.display());
+            self.detach();
+            write_to_rwlock(&self.source_missing, false);
+            if !self.attach() {
+                println!("Error watching {} again", path.display());
+                write_to_rwlock(&self.source_missing, true);
+                return false;
+            }
+            if self.parent_watcher.lock().unwrap().is_none() {
+                self.start_parent_watcher();
+            }
+            write_to_rwlock(&self.state.has_changes, true);
+            write_to_rwlock(&self.state.latest_change, chrono::Utc::now().timestamp_millis());
+            missing
+        } else {
+            false
+        }
+    }
+
+    /// Checks every few seconds whether the native backend still delivers events, by comparing
+    /// sizes and modification times of the watched files. If files changed without an event,
+    /// or the backend reported an error, polling is started in addition. Returns `true` if it
+    /// fell back to polling with this call.
+    pub fn check_native_events(&self) -> bool {
+        let Some(path) = self.path.as_ref() else {
+            return false;
+        };
+        if self.is_polling() || self.is_paused() || read_rwlock_or(&self.source_missing, false) {
+            return false;
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut check = self.native_check.lock().unwrap();
+        if now - check.checked_at < NATIVE_CHECK_INTERVAL {
+            return false;
+        }
+
+        let files = backup::list_source_files(path, &self.filter).ok().map(|files| {
+            files.into_iter().map(|(relative_path, file)| {
+                let metadata = file.metadata().ok();
+                let modified = metadata.as_ref().and_then(|m| m.modified().ok()).and_then(millis_from_system_time);
+                (relative_path, metadata.map(|m| m.len()).unwrap_or(0), modified)
+            }).collect::<Vec<_>>()
+        });
+        let missed_events = match (&check.files, &files) {
+            (Some(old), Some(new)) => old != new && read_rwlock_or(&self.state.latest_native_event, 0) < check.checked_at,
+            _ => false,
+        };
+        let failed = read_rwlock_or(&self.state.native_failed, false);
+        check.checked_at = now;
+        check.files = files;
+        drop(check);
+
+        if !missed_events && !failed {
+            return false;
+        }
+
+        println!("Native file events of {} stopped arriving, falling back to polling", path.display());
+        if self.start_polling() {
+            write_to_rwlock(&self.fell_back, true);
+            // The change that went unnoticed still has to be backed up
+            if missed_events {
+                write_to_rwlock(&self.state.has_changes, true);
+                write_to_rwlock(&self.state.latest_change, now);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether files changed since the last call to [`SourceWatcher::clear_changes`].
+    pub fn has_changes(&self) -> bool {
+        read_rwlock_or(&self.state.has_changes, false)
+    }
+
+    /// Time of the latest change in milliseconds since the epoch.
+    pub fn latest_change(&self) -> i64 {
+        read_rwlock_or(&self.state.latest_change, 0)
+    }
+
+    pub fn clear_changes(&self) {
+        write_to_rwlock(&self.state.has_changes, false);
+    }
+
+    /// While paused all file events are ignored, e.g. while a backup is being restored.
+    pub fn set_paused(&self, paused: bool) {
+        write_to_rwlock(&self.state.paused, paused);
+        if !paused {
+            // Changes made while paused are not missed events
+            self.native_check.lock().unwrap().files = None;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        read_rwlock_or(&self.state.paused, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    fn start(src: &Path, dst: &Path, backend: WatcherBackend) -> SourceWatcher {
+        let mut watcher = SourceWatcher::new();
+        assert!(watcher.start(&src.to_string_lossy(), &dst.to_string_lossy(), FileFilter::default(), backend, POLL_INTERVAL));
+        watcher
+    }
+
+    /// Waits up to a few poll intervals for the watcher to notice a change.
+    fn noticed_change(watcher: &SourceWatcher) -> bool {
+        for _ in 0..40 {
+            if watcher.has_changes() {
+                return true;
+            }
+            std::thread::sleep(POLL_INTERVAL / 2);
+        }
+        false
+    }
+
+    #[test]
+    fn polling_notices_changed_files() {
+        let src = test_dir("watcher_polling_src");
+        let dst = test_dir("watcher_polling_dst");
+        std::fs::write(src.join("slot1.sav"), "first").unwrap();
+        let watcher = start(&src, &dst, WatcherBackend::Polling);
+        assert!(watcher.is_polling());
+        assert!(!watcher.has_changes());
+
+        // Polling compares modification times in whole seconds, as a later save would have it
+        std::fs::write(src.join("slot1.sav"), "second pass").unwrap();
+        let file = std::fs::File::options().write(true).open(src.join("slot1.sav")).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(2)).unwrap();
+        assert!(noticed_change(&watcher));
+        assert_eq!(watcher.health(), WatcherHealth::Watching);
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn hybrid_notices_changes_from_either_backend() {
+        let src = test_dir("watcher_hybrid_src");
+        let dst = test_dir("watcher_hybrid_dst");
+
+        let watcher = start(&src, &dst, WatcherBackend::Hybrid);
+        *watcher.watcher.lock().unwrap() = None;
+        std::fs::write(src.join("slot1.sav"), "polled").unwrap();
+        assert!(noticed_change(&watcher));
+
+        let watcher = start(&src, &dst, WatcherBackend::Hybrid);
+        *watcher.poller.lock().unwrap() = None;
+        std::fs::write(src.join("slot2.sav"), "native").unwrap();
+        assert!(noticed_change(&watcher));
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn failed_native_attach_falls_back_to_polling() {
+        let src = test_dir("watcher_fallback_src");
+        let dst = test_dir("watcher_fallback_dst");
+        let mut watcher = start(&src, &dst, WatcherBackend::Native);
+        assert!(!watcher.is_polling());
+
+        // Watching a folder that is gone fails like running out of watches would
+        watcher.detach();
+        watcher.path = Some(src.join("missing"));
+        assert!(watcher.attach());
+        assert!(watcher.check_native_events());
+        assert_eq!(watcher.health(), WatcherHealth::FellBackToPolling);
+        assert!(watcher.is_polling());
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn reattaches_once_the_source_folder_is_back() {
+        let root = test_dir("watcher_source_missing");
+        let src = root.join("save");
+        let dst = root.join("backups");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::create_dir(&dst).unwrap();
+        let watcher = start(&src, &dst, WatcherBackend::Native);
+
+        std::fs::remove_dir_all(&src).unwrap();
+        assert!(watcher.check_source());
+        assert_eq!(watcher.health(), WatcherHealth::SourceMissing);
+        assert!(!watcher.check_source());
+
+        std::fs::create_dir(&src).unwrap();
+        assert!(watcher.check_source());
+        assert_eq!(watcher.health(), WatcherHealth::Watching);
+        assert!(watcher.has_changes());
+
+        watcher.clear_changes();
+        std::fs::write(src.join("slot1.sav"), "after the folder came back").unwrap();
+        assert!(noticed_change(&watcher));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn only_the_source_folder_itself_counts_in_its_parent() {
+        use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode};
+
+        let root = test_dir("watcher_parent_events");
+        let src = root.join("save");
+        let state = Arc::new(WatcherState::default());
+        let mut handler = SourceParentEventHandler { state: state.clone(), root: src.clone(), canonical_root: src.clone() };
+        let event = |kind: notify::EventKind, path: &PathBuf| Ok(notify::Event::new(kind).add_path(path.clone()));
+
+        notify::EventHandler::handle_event(&mut handler, event(notify::EventKind::Modify(ModifyKind::Data(DataChange::Any)), &src));
+        notify::EventHandler::handle_event(&mut handler, event(notify::EventKind::Create(CreateKind::Folder), &root.join("other")));
+        notify::EventHandler::handle_event(&mut handler, event(notify::EventKind::Remove(RemoveKind::Folder), &root.join("other")));
+        assert!(!read_rwlock_or(&state.source_replaced, false));
+
+        notify::EventHandler::handle_event(&mut handler, event(notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)), &src));
+        assert!(read_rwlock_or(&state.source_replaced, false));
+        write_to_rwlock(&state.source_replaced, false);
+        notify::EventHandler::handle_event(&mut handler, event(notify::EventKind::Create(CreateKind::Folder), &src));
+        assert!(read_rwlock_or(&state.source_replaced, false));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,10 +1,12 @@
 use crate::*;
 use backup::SavegameMeta;
+use profile::{ProfileIntervalUnit, SavegameManagerProfile, DATA_FILE};
+use retention::RetentionPolicy;
+use storage::StorageMode;
+use watcher::WatcherBackend;
 
 use std::cell::RefMut;
-use std::{cell::{Ref, RefCell}, fs::File, path::PathBuf};
-
-use serde::{Deserialize, Serialize};
+use std::cell::{Ref, RefCell};
 
 use native_windows_gui as nwg;
 use native_windows_derive as nwd;
@@ -13,75 +15,6 @@ use nwg::{NativeUi, stretch::{geometry::{Size, Rect}, style::{Dimension as D, Fl
 
 const NO_PADDING: Rect<D> = Rect { start: D::Points(0.0), end: D::Points(0.0), top: D::Points(0.0), bottom: D::Points(0.0) };
 const PADDING_LEFT: Rect<D> = Rect { start: D::Points(5.0), end: D::Points(0.0), top: D::Points(0.0), bottom: D::Points(0.0) };
-const DATA_FILE: &str = "savegame_manager.json";
-
-#[derive(Clone, Serialize, Deserialize)]
-pub enum ProfileIntervalUnit {
-    Seconds,
-    Minutes,
-    Hours,
-}
-
-impl Default for ProfileIntervalUnit {
-    fn default() -> Self { ProfileIntervalUnit::Minutes }
-}
-
-impl std::fmt::Display for ProfileIntervalUnit {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Self::Seconds => "seconds",
-            Self::Minutes => "minutes",
-            Self::Hours => "hours"
-        })
-    }
-}
-
-#[derive(Default, Deserialize)]
-#[serde(default)]
-struct OldSavegameManagerSettings {
-    source_path: String,
-    dest_path: String,
-    disable_screenshots: bool,
-}
-
-fn default_true() -> bool { true }
-
-#[derive(Serialize, Deserialize)]
-#[serde(default)]
-struct SavegameManagerProfile {
-    selected: bool,
-    name: String,
-    src_path: String,
-    dst_path: String,
-    #[serde(default = "default_true")] screenshots: bool,
-    #[serde(default = "default_true")] manual_save_detection: bool,
-    auto_saves_max: u16,
-    auto_saves_interval: u16,
-    auto_saves_interval_unit: ProfileIntervalUnit,
-}
-
-impl std::fmt::Display for SavegameManagerProfile {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name)
-    }
-}
-
-impl Default for SavegameManagerProfile {
-    fn default() -> Self {
-        Self {
-            selected: Default::default(),
-            name: Default::default(),
-            src_path: Default::default(),
-            dst_path: Default::default(),
-            screenshots: true,
-            manual_save_detection: true,
-            auto_saves_max: 12,
-            auto_saves_interval: 5,
-            auto_saves_interval_unit: Default::default()
-        }
-    }
-}
-
 enum RenameMode {
     Backup,
     Profile,
@@ -95,8 +28,14 @@ impl Default for RenameMode {
 pub struct SavegameManagerApp {
     dummy_profile: RefCell<SavegameManagerProfile>,
     profiles_changed: RefCell<bool>,
+    /// Settings typed into a text input are handed to the engine once the input lost focus.
+    engine_profile_pending: RefCell<bool>,
     selected_backup: RefCell<Option<String>>,
     rename_mode: RefCell<RenameMode>,
+    /// Backups shown in the diff dialog, `None` as new side for the live files, and the paths of its rows.
+    compared_backups: RefCell<Option<(SavegameMeta, Option<SavegameMeta>)>>,
+    compared_files: RefCell<Vec<String>>,
+    engine: RefCell<BackupEngine>,
 
     #[nwg_resource(family: "Segoe UI Semibold", size: 16, weight: 400)]
     font_bold: nwg::Font,
@@ -114,7 +53,7 @@ pub struct SavegameManagerApp {
     #[nwg_resource(source_bin: Some(include_bytes!("../assets/no_screenshot.png")), size: Some((295, 166)))]
     no_screenshot: nwg::Bitmap,
 
-    #[nwg_control(size: (800, 600), title: "Savegame Manager", flags: "MAIN_WINDOW", icon: Some(&data.window_icon))]
+    #[nwg_control(size: (800, 800), title: "Savegame Manager", flags: "MAIN_WINDOW", icon: Some(&data.window_icon))]
     #[nwg_events( OnWindowClose: [SavegameManagerApp::exit] )]
     window: nwg::Window,
 
@@ -244,6 +183,220 @@ pub struct SavegameManagerApp {
     autosave_interval_unit: nwg::ComboBox<ProfileIntervalUnit>,
 // endregion
 
+// region: stability settings
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0)})]
+    stability_frame: nwg::Frame,
+
+    #[nwg_layout(parent: stability_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    stability_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: stability_frame, text: "Wait (ms): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: stability_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    stability_lbl_quiet_period: nwg::Label,
+
+    #[nwg_control(parent: stability_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: stability_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::stability_text_input(SELF, HANDLE)])]
+    stability_quiet_period: nwg::TextInput,
+
+    #[nwg_control(parent: stability_frame, text: "then unchanged for: ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: stability_layout, size: Size { width: D::Points(110.0), height: D::Auto })]
+    stability_lbl_polls: nwg::Label,
+
+    #[nwg_control(parent: stability_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: stability_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::stability_text_input(SELF, HANDLE)])]
+    stability_polls: nwg::TextInput,
+
+    #[nwg_control(parent: stability_frame, text: "checks", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: stability_layout, size: Size { width: D::Points(100.0), height: D::Auto }, margin: PADDING_LEFT)]
+    stability_lbl_checks: nwg::Label,
+// endregion
+
+// region: retention settings
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    retention_frame: nwg::Frame,
+
+    #[nwg_layout(parent: retention_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    retention_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: retention_frame, text: "Thin out old backups")]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Points(150.0), height: D::Auto })]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::retention_checkbox_click])]
+    retention_check: nwg::CheckBox,
+
+    #[nwg_control(parent: retention_frame, text: "Keep all (min.): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_keep_all: nwg::Label,
+
+    #[nwg_control(parent: retention_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_keep_all: nwg::TextInput,
+
+    #[nwg_control(parent: retention_frame, text: "hourly (hours): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_hourly: nwg::Label,
+
+    #[nwg_control(parent: retention_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_hourly: nwg::TextInput,
+
+    #[nwg_control(parent: retention_frame, text: "daily (days): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_daily: nwg::Label,
+
+    #[nwg_control(parent: retention_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_daily: nwg::TextInput,
+
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    retention_limits_frame: nwg::Frame,
+
+    #[nwg_layout(parent: retention_limits_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    retention_limits_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: retention_limits_frame, text: "weekly (weeks): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_weekly: nwg::Label,
+
+    #[nwg_control(parent: retention_limits_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_weekly: nwg::TextInput,
+
+    #[nwg_control(parent: retention_limits_frame, text: "monthly (months): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    retention_lbl_monthly: nwg::Label,
+
+    #[nwg_control(parent: retention_limits_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_monthly: nwg::TextInput,
+
+    #[nwg_control(parent: retention_limits_frame, text: "max. count: ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_max_count: nwg::Label,
+
+    #[nwg_control(parent: retention_limits_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_max_count: nwg::TextInput,
+
+    #[nwg_control(parent: retention_limits_frame, text: "max. size (MB): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Points(90.0), height: D::Auto })]
+    retention_lbl_max_size: nwg::Label,
+
+    #[nwg_control(parent: retention_limits_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: retention_limits_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::retention_text_input(SELF, HANDLE)])]
+    retention_max_size: nwg::TextInput,
+
+    #[nwg_control(parent: window, text: "", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
+    retention_report: nwg::Label,
+// endregion
+
+// region: storage settings
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    storage_frame: nwg::Frame,
+
+    #[nwg_layout(parent: storage_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    storage_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: storage_frame, text: "Storage:", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: storage_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    storage_label: nwg::Label,
+
+    #[nwg_control(parent: storage_frame)]
+    #[nwg_layout_item(layout: storage_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    #[nwg_events(OnComboxBoxSelection: [SavegameManagerApp::storage_mode_select_change])]
+    storage_mode: nwg::ComboBox<StorageMode>,
+// endregion
+
+// region: watcher settings
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    watcher_frame: nwg::Frame,
+
+    #[nwg_layout(parent: watcher_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    watcher_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: watcher_frame, text: "Watcher:", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: watcher_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    watcher_label: nwg::Label,
+
+    #[nwg_control(parent: watcher_frame)]
+    #[nwg_layout_item(layout: watcher_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    #[nwg_events(OnComboxBoxSelection: [SavegameManagerApp::watcher_backend_select_change])]
+    watcher_backend: nwg::ComboBox<WatcherBackend>,
+
+    #[nwg_control(parent: watcher_frame, text: "poll every (ms): ", h_align: nwg::HTextAlign::Right, v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: watcher_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    watcher_lbl_poll_interval: nwg::Label,
+
+    #[nwg_control(parent: watcher_frame, flags: "VISIBLE|NUMBER")]
+    #[nwg_layout_item(layout: watcher_layout, size: Size { width: D::Points(80.0), height: D::Auto }, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::poll_interval_text_input])]
+    watcher_poll_interval: nwg::TextInput,
+
+    #[nwg_control(parent: watcher_frame, text: "", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: watcher_layout, size: Size { width: D::Points(200.0), height: D::Auto }, margin: PADDING_LEFT)]
+    watcher_status: nwg::Label,
+// endregion
+
+// region: file patterns
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    patterns_frame: nwg::Frame,
+
+    #[nwg_layout(parent: patterns_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    patterns_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: patterns_frame, text: "Files:", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: patterns_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    patterns_label: nwg::Label,
+
+    #[nwg_control(parent: patterns_frame, placeholder_text: Some("Include, e.g. *.sav, slot*/"))]
+    #[nwg_layout_item(layout: patterns_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::patterns_text_input])]
+    include_patterns: nwg::TextInput,
+
+    #[nwg_control(parent: patterns_frame, placeholder_text: Some("Exclude, e.g. *.log, cache/"))]
+    #[nwg_layout_item(layout: patterns_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0, margin: PADDING_LEFT)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::patterns_text_input])]
+    exclude_patterns: nwg::TextInput,
+// endregion
+
+// region: backup filter
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    filter_frame: nwg::Frame,
+
+    #[nwg_layout(parent: filter_frame, flex_direction: FlexDirection::Row, padding: NO_PADDING)]
+    filter_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: filter_frame, text: "Filter:", v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: filter_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    filter_label: nwg::Label,
+
+    #[nwg_control(parent: filter_frame, placeholder_text: Some("Name, notes or #tag"))]
+    #[nwg_layout_item(layout: filter_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    #[nwg_events(OnTextInput: [SavegameManagerApp::filter_text_input])]
+    backup_filter: nwg::TextInput,
+
+    #[nwg_control(parent: filter_frame, text: "Repair backups")]
+    #[nwg_layout_item(layout: filter_layout, size: Size { width: D::Points(110.0), height: D::Auto }, margin: PADDING_LEFT)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::repair_click])]
+    repair_backups: nwg::Button,
+// endregion
+
     #[nwg_control(parent: window, flags: "VISIBLE")]
     #[nwg_layout_item(layout: layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
     savegame_frame: nwg::Frame,
@@ -279,14 +432,31 @@ pub struct SavegameManagerApp {
     #[nwg_control(parent: savegame_detail_frame, text: "-", v_align: nwg::VTextAlign::Top)]
     #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
     savegame_detail_date_content: nwg::Label,
+
+    #[nwg_control(parent: savegame_detail_frame, text: "Tags:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
+    savegame_detail_tags: nwg::Label,
+
+    #[nwg_control(parent: savegame_detail_frame, placeholder_text: Some("Tags, separated by commas"))]
+    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(23.0) })]
+    savegame_detail_tags_content: nwg::TextInput,
+
+    #[nwg_control(parent: savegame_detail_frame, text: "Notes:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
+    savegame_detail_notes: nwg::Label,
+
+    #[nwg_control(parent: savegame_detail_frame, flags: "VISIBLE|VSCROLL|AUTOVSCROLL")]
+    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(50.0) })]
+    savegame_detail_notes_content: nwg::TextBox,
     
-    #[nwg_control(parent: savegame_detail_frame, text: "Checksums:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_control(parent: savegame_detail_frame, text: "Files:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
     #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
     savegame_detail_checksums: nwg::Label,
 
-    #[nwg_control(parent: savegame_detail_frame, text: "-", font: Some(&data.font_monospace), flags: "VISIBLE|MULTI_LINE")]
+    #[nwg_control(parent: savegame_detail_frame, font: Some(&data.font_monospace), flags: "VISIBLE|MULTI_SELECT")]
     #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
-    savegame_detail_checksums_content: nwg::RichLabel,
+    #[nwg_events(OnListBoxSelect: [SavegameManagerApp::file_select_change])]
+    savegame_detail_files: nwg::ListBox<String>,
     
     #[nwg_control(parent: savegame_detail_frame, bitmap: Some(&data.no_screenshot))]
     #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Points(295.0), height: D::Points(166.0) })]
@@ -294,7 +464,7 @@ pub struct SavegameManagerApp {
     savegame_detail_screenshot: nwg::ImageFrame,
 
     #[nwg_control(parent: savegame_detail_frame, flags: "VISIBLE")]
-    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(30.0) })]
+    #[nwg_layout_item(layout: savegame_detail_layout, size: Size { width: D::Auto, height: D::Points(120.0) })]
     savegame_btns_frame: nwg::Frame,
 
     #[nwg_layout(parent: savegame_btns_frame, margin: [5, 0, 0, 0], spacing: 0)]
@@ -314,10 +484,71 @@ pub struct SavegameManagerApp {
     #[nwg_layout_item(layout: savegame_btns_layout, row: 0, col: 2)]
     #[nwg_events(OnButtonClick: [SavegameManagerApp::delete_click])]
     savegame_delete: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Verify", enabled: false)]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 1, col: 0)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::verify_click])]
+    savegame_verify: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Pin", enabled: false)]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 1, col: 1)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::pin_click])]
+    savegame_pin: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Undo load")]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 1, col: 2)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::undo_load_click])]
+    savegame_undo_load: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Save tags and notes", enabled: false)]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 2, col: 0, col_span: 2)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::save_annotations_click])]
+    savegame_save_annotations: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Compare", enabled: false)]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 2, col: 2)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::compare_click])]
+    savegame_compare: nwg::Button,
+
+    #[nwg_control(parent: savegame_btns_frame, text: "Load selected files", enabled: false)]
+    #[nwg_layout_item(layout: savegame_btns_layout, row: 3, col: 0, col_span: 3)]
+    #[nwg_events(OnButtonClick: [SavegameManagerApp::load_files_click])]
+    savegame_load_files: nwg::Button,
+// endregion
+
+// region: diff dialog
+    #[nwg_control(parent: Some(&data.window), size: (700, 400), title: "Compare backups", flags: "WINDOW|RESIZABLE", icon: Some(&data.window_icon))]
+    #[nwg_events(OnKeyEsc: [SavegameManagerApp::diff_close])]
+    diff_dialog: nwg::Window,
+
+    #[nwg_layout(parent: diff_dialog, flex_direction: FlexDirection::Column)]
+    diff_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: diff_dialog, text: "-", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Center)]
+    #[nwg_layout_item(layout: diff_layout, size: Size { width: D::Auto, height: D::Points(20.0) })]
+    diff_summary: nwg::Label,
+
+    #[nwg_control(parent: diff_dialog, list_style: nwg::ListViewStyle::Detailed, ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT | nwg::ListViewExFlags::GRID)]
+    #[nwg_layout_item(layout: diff_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    #[nwg_events(OnKeyRelease: [SavegameManagerApp::diff_key_released(SELF, EVT_DATA)], OnListViewDoubleClick: [SavegameManagerApp::diff_file_open])]
+    diff_list: nwg::ListView,
+// endregion
+
+// region: hex dialog
+    #[nwg_control(parent: Some(&data.window), size: (950, 550), title: "Compare bytes", flags: "WINDOW|RESIZABLE", icon: Some(&data.window_icon))]
+    #[nwg_events(OnKeyEsc: [SavegameManagerApp::hex_close])]
+    hex_dialog: nwg::Window,
+
+    #[nwg_layout(parent: hex_dialog, flex_direction: FlexDirection::Column)]
+    hex_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: hex_dialog, text: "", readonly: true, font: Some(&data.font_monospace), flags: "VISIBLE|VSCROLL|HSCROLL|AUTOVSCROLL|AUTOHSCROLL")]
+    #[nwg_layout_item(layout: hex_layout, size: Size { width: D::Auto, height: D::Auto }, flex_grow: 1.0)]
+    hex_view: nwg::TextBox,
 // endregion
 
 // region: rename dialog
-    #[nwg_control(parent: Some(&data.window), size: (300, 120), title: "Rename backup", flags: "WINDOW", icon: Some(&data.window_icon))]
+    #[nwg_control(parent: Some(&data.window), size: (300, 180), title: "Rename backup", flags: "WINDOW", icon: Some(&data.window_icon))]
     #[nwg_events(OnKeyEsc: [SavegameManagerApp::rename_cancel(SELF, EVT)], OnKeyEnter: [SavegameManagerApp::rename_confirm])]
     rename_dialog: nwg::Window,
 
@@ -328,12 +559,20 @@ pub struct SavegameManagerApp {
     #[nwg_layout_item(layout: rename_layout, row: 0, col: 0, col_span: 2)]
     rename_input: nwg::TextInput,
 
+    #[nwg_control(parent: rename_dialog, placeholder_text: Some("Tags, separated by commas"))]
+    #[nwg_layout_item(layout: rename_layout, row: 1, col: 0, col_span: 2)]
+    rename_tags: nwg::TextInput,
+
+    #[nwg_control(parent: rename_dialog, placeholder_text: Some("Notes"))]
+    #[nwg_layout_item(layout: rename_layout, row: 2, col: 0, col_span: 2)]
+    rename_notes: nwg::TextInput,
+
     #[nwg_control(parent: rename_dialog, text: "Rename")]
-    #[nwg_layout_item(layout: rename_layout, row: 1, col: 0)]
+    #[nwg_layout_item(layout: rename_layout, row: 3, col: 0)]
     rename_btn: nwg::Button,
 
     #[nwg_control(parent: rename_dialog, text: "Cancel")]
-    #[nwg_layout_item(layout: rename_layout, row: 1, col: 1)]
+    #[nwg_layout_item(layout: rename_layout, row: 3, col: 1)]
     #[nwg_events(OnButtonClick: [SavegameManagerApp::rename_cancel(SELF, EVT)])]
     rename_cancel: nwg::Button,
 // endregion
@@ -361,234 +600,162 @@ impl SavegameManagerApp {
         let profiles_changed = self.profiles_changed.borrow();
 
         if *profiles_changed {
-            if let Ok(file) = File::create(DATA_FILE) {
-                serde_json::to_writer_pretty(file, &*self.profile_select.collection()).unwrap_or_default();
+            if let Err(err) = profile::save_profiles(DATA_FILE, &*self.profile_select.collection()) {
+                println!("Error saving config file: {:?}", err);
             }
         }
 
-        backup::deal_with_exit_save(&self.get_current_profile().dst_path);
-
-        let screenshot_file = PathBuf::from("screenshot.jpg");
-        if screenshot_file.exists() && screenshot_file.is_file() {
-            std::fs::remove_file(screenshot_file).unwrap_or_default();
-        }
-
+        self.engine.borrow_mut().shutdown();
 
         nwg::stop_thread_dispatch();
     }
 
     fn start_watcher(&self) {
-        let profile = self.get_current_profile();
-        if !watcher::start_watcher(&profile.src_path, &profile.dst_path) {
+        let started = self.engine.borrow_mut().start_watcher();
+        if !started {
             nwg::modal_error_message(&self.window, "Watcher error", "Could not start folder monitoring");
         }
+        self.show_watcher_health();
     }
 
-    fn tick_screenshot(&self) -> bool {
-        if self.get_current_profile().screenshots {
-            let state = read_rwlock_or(&SCREENSHOT_STATE, screenshot::ScreenshotState::Idle);
-            match state {
-                screenshot::ScreenshotState::Idle => {
-                    println!("Taking screenshot");
-                    write_to_rwlock(&SCREENSHOT_STATE, screenshot::ScreenshotState::Busy);
-                    std::thread::spawn(screenshot::create_screenshot);
-                    true
-                },
-                screenshot::ScreenshotState::Busy => {
-                    true
-                },
-                screenshot::ScreenshotState::Error => {
-                    let error = read_rwlock_or(&SCREENSHOT_ERROR, "Unknown error occured while taking screenshot");
-                    nwg::modal_error_message(&self.window.handle, "Screenshot error", error);
-                    false
-                },
-                screenshot::ScreenshotState::Finished => {
-                    false
-                },
-            }
-        } else {
-            false
-        }
+    fn show_watcher_health(&self) {
+        self.watcher_status.set_text(&self.engine.borrow().watcher_health().to_string());
     }
 
-    fn finish_up_backup(&self) {
-        let error = read_rwlock_or(&BACKUP_ERROR, String::new());
-        if error.len() > 0 {
-            nwg::modal_error_message(&self.window.handle, "Backup error", error.as_str());
-        } else {
-            let backup_name = read_rwlock_or(&BACKUP_NAME, String::new());
-            if backup_name.len() > 0 {
-                let dst_path = self.get_current_profile().dst_path.clone();
-                match backup::get_meta_for_backup(&dst_path, &backup_name) {
-                    Ok(_) => {
-                        self.refresh_backup_list();
-                    },
-                    Err(err) => {
-                        nwg::modal_error_message(&self.window, "Backup error", format!("Error reading backup meta: {:?}", err).as_str());
-                    }
-                }
-            }
+    /// Hands changed profile settings over to the engine, which restarts the watcher if needed.
+    fn sync_engine_profile(&self) {
+        *self.engine_profile_pending.borrow_mut() = false;
+        let profile = self.get_current_profile().clone();
+        let started = self.engine.borrow_mut().set_profile(profile);
+        if !started {
+            nwg::modal_error_message(&self.window, "Watcher error", "Could not start folder monitoring");
         }
-
-        println!("Finishing up");
-        write_to_rwlock(&BACKUP_STATE, backup::BackupState::Idle);
-        write_to_rwlock(&WATCHER_HAS_CHANGES, false);
+        self.show_watcher_health();
     }
 
-    fn start_backup(&self, src_path: String, dst_path: String, copy_screenshot: bool, wait: bool) {
-        let mut backup_type: u8 = 0;
-
-        if self.get_current_profile().manual_save_detection {
-            let live_hashes = backup::create_hash_list(&src_path);
-
-            if let Some(savegame) = self.savegame_list.get_latest_non_temp() {
-                let hash_cmp = backup::hash_list_cmp(&live_hashes, &savegame.checksums);
-                match hash_cmp {
-                    backup::BackupComparison::CompleteDiff => { backup_type = 0; },
-                    backup::BackupComparison::PartialDiff => {
-                        let profile = self.get_current_profile();
-                        if chrono::Local::now().timestamp_millis() - savegame.date > interval_duration(profile.auto_saves_interval, &profile.auto_saves_interval_unit) {
-                            backup_type = 2;
-                        } else {
-                            backup_type = 1;
-                        }
-                    },
-                    backup::BackupComparison::NoDiff => { backup_type = 3; },
-                }
-            }
-        }
-
-        let autosave_max = self.get_current_profile().auto_saves_max.clone();
-        let fun = move || {
-            match backup_type {
-                0 => backup::create_savetokeep(&src_path, &dst_path, &copy_screenshot),
-                1 => backup::create_tempsave(&src_path, &dst_path, &copy_screenshot),
-                2 => backup::create_autosave(&src_path, &dst_path, &copy_screenshot, &autosave_max),
-                _ => {}
-            }
-        };
+    /// Whether one of the text inputs of the profile settings has the keyboard focus.
+    fn settings_input_focused(&self) -> bool {
+        [
+            &self.autosave_amount, &self.autosave_interval, &self.stability_quiet_period, &self.stability_polls,
+            &self.retention_keep_all, &self.retention_hourly, &self.retention_daily, &self.retention_weekly,
+            &self.retention_monthly, &self.retention_max_count, &self.retention_max_size,
+            &self.watcher_poll_interval, &self.include_patterns, &self.exclude_patterns,
+        ].iter().any(|input| input.focus())
+    }
 
-        if wait {
-            fun();
-        } else {
-            std::thread::spawn(fun);
+    fn timer_tick(&self) {
+        let pending = *self.engine_profile_pending.borrow();
+        if pending && !self.settings_input_focused() {
+            self.sync_engine_profile();
         }
-    }
 
-    fn tick_backup(&self, wait_for_screenshot: bool) {
-        match read_rwlock_or(&BACKUP_STATE, backup::BackupState::Idle) {
-            backup::BackupState::Idle => {
-                let now = chrono::Utc::now().timestamp_millis();
-                let last_change = read_rwlock_or(&WATCHER_LATEST_CHANGE, now);
-                if now - last_change > 1_000 && !wait_for_screenshot {
-                    println!("Creating backup");
-                    write_to_rwlock(&BACKUP_STATE, backup::BackupState::Busy);
-                    let data = self.get_current_profile();
-                    let src_path = data.src_path.clone();
-                    let dst_path = data.dst_path.clone();
-                    let copy_screenshot = data.screenshots;
-                    drop(data);
-
-                    self.start_backup(src_path, dst_path, copy_screenshot, false);
-                }
+        let event = self.engine.borrow().tick();
+        match event {
+            Some(EngineEvent::BackupCreated(_)) => {
+                self.refresh_backup_list();
+                self.show_prune_report();
             },
-            backup::BackupState::Busy => {},
-            backup::BackupState::Finished => {
-                self.finish_up_backup();
-            }
+            Some(EngineEvent::BackupFailed(error)) => {
+                nwg::modal_error_message(&self.window.handle, "Backup error", error.as_str());
+            },
+            Some(EngineEvent::ScreenshotFailed(error)) => {
+                nwg::modal_error_message(&self.window.handle, "Screenshot error", error.as_str());
+            },
+            Some(EngineEvent::WatcherHealthChanged(_)) => {
+                self.show_watcher_health();
+            },
+            None => {}
         }
+
+        self.timer.start();
     }
 
-    fn timer_tick(&self) {
-        let changes = read_rwlock_or(&WATCHER_HAS_CHANGES, false);
-        if changes {
-            self.tick_backup(self.tick_screenshot());
+    /// Lists the backups removed after the latest backup and why.
+    fn show_prune_report(&self) {
+        let report = self.engine.borrow().last_prune_report();
+        if !report.is_empty() {
+            let removed: Vec<String> = report.removed.iter().map(|pruned| format!("{} ({})", pruned.name, pruned.reason)).collect();
+            self.retention_report.set_text(&format!("Removed after the last backup: {}", removed.join(", ")));
         }
-
-        self.timer.start();
     }
 
     fn refresh_backup_list(&self) {
-        let profile = self.get_current_profile();
-        let dst_path = profile.dst_path.clone();
+        let dst_path = self.get_current_profile().dst_path.clone();
 
         if dst_path.len() == 0 {
             self.savegame_list.clear_list(true);
             return;
         }
 
-        match backup::look_for_backups(&dst_path) {
+        let result = self.engine.borrow().refresh_backups();
+        match result {
             Ok(backups) => {
-                self.savegame_list.set_redraw(false);
-                self.savegame_list.clear_list(false);
-                for backup in backups {
-                    self.savegame_list.push_savegame(backup);
+                if !self.show_backups(backups) {
+                    self.engine.borrow().start_backup(false);
                 }
+            },
+            Err(err) => {
+                nwg::modal_error_message(&self.window, "Backup error", format!("Error reading backups: {:?}", err).as_str());
+            }
+        }
+    }
 
-                self.savegame_list.update_list(false);
-                self.savegame_list.set_redraw(true);
+    /// Shows the backups matching the filter and marks the one that matches the live files.
+    /// Returns whether any backup, shown or not, matches the live files.
+    fn show_backups(&self, backups: Vec<SavegameMeta>) -> bool {
+        let filter = self.backup_filter.text();
 
-                let src_path = profile.src_path.clone();
+        self.savegame_list.set_redraw(false);
+        self.savegame_list.clear_list(false);
+        for backup in backups {
+            if backup.matches_filter(&filter) {
+                self.savegame_list.push_savegame(backup);
+            }
+        }
 
-                let mut found_current_backup = false;
-                let live_hashes = backup::create_hash_list(&src_path);
-                for (i, backup) in (&*self.savegame_list.data.borrow()).iter().enumerate() {
-                    if !!backup::hash_list_cmp(&live_hashes, &backup.checksums) {
-                        self.savegame_list.check_row(i);
-                        found_current_backup = true;
-                        break;
-                    }
-                }
+        self.savegame_list.update_list(false);
+        self.savegame_list.set_redraw(true);
 
-                if !found_current_backup {
-                    self.start_backup(src_path, dst_path, false, false);
-                    self.finish_up_backup();
+        let live_backup = self.engine.borrow().find_live_backup();
+        match live_backup {
+            Some(live_backup) => {
+                let index = self.savegame_list.data.borrow().iter().position(|b| b.name == live_backup.name);
+                if let Some(index) = index {
+                    self.savegame_list.check_row(index);
                 }
-
+                true
             },
-            Err(err) => {
-                nwg::modal_error_message(&self.window, "Backup error", format!("Error reading backups: {:?}", err).as_str());
-            }
+            None => false,
         }
     }
 
+    fn filter_text_input(&self) {
+        let backups = self.engine.borrow().backups();
+        self.show_backups(backups);
+    }
+
     fn load_data(&self) {
         self.tooltip.register_callback(&self.profile_add);
         self.tooltip.register_callback(&self.profile_rename);
         self.tooltip.register_callback(&self.profile_remove);
+        self.tooltip.register(&self.retention_check, "Applies to every backup except pinned backups and pre-restore snapshots, including kept and manual backups.\r\nTemporary saves are removed with every backup, whether this is checked or not.");
+
+        for (index, (title, width)) in [("File", 220), ("Change", 80), ("Old size", 80), ("New size", 80), ("Old modified", 110), ("New modified", 110)].into_iter().enumerate() {
+            self.diff_list.insert_column(nwg::InsertListViewColumn { index: Some(index as i32), fmt: Some(nwg::ListViewColumnFlags::LEFT), width: Some(width), text: Some(title.to_owned()) });
+        }
 
         self.autosave_interval_unit.set_collection(vec![ProfileIntervalUnit::Seconds, ProfileIntervalUnit::Minutes, ProfileIntervalUnit::Hours]);
+        self.storage_mode.set_collection(vec![StorageMode::Folder, StorageMode::Deduplicated, StorageMode::Zip, StorageMode::TarZst]);
+        self.watcher_backend.set_collection(vec![WatcherBackend::Native, WatcherBackend::Polling, WatcherBackend::Hybrid]);
 
-        let mut profiles: Vec<SavegameManagerProfile> = match File::open(DATA_FILE) {
-            Ok(file) => {
-                match serde_json::from_reader::<File, Vec<SavegameManagerProfile>>(file) {
-                    Ok(json) => {
-                        json
-                    },
-                    Err(err) => {
-                        match serde_json::from_reader::<File, OldSavegameManagerSettings>(File::open(DATA_FILE).unwrap()) {
-                            Ok(json) => {
-                                let mut p: SavegameManagerProfile = Default::default();
-                                p.name = "Default".to_owned();
-                                p.selected = true;
-                                p.src_path = json.source_path;
-                                p.dst_path = json.dest_path;
-                                p.screenshots = !json.disable_screenshots;
-                                vec![p]
-                            },
-                            Err(_) => {
-                                nwg::modal_error_message(&self.window, "Config file error", format!("Unable to parse config file. {:?}", err).as_str());
-                                vec![]
-                            }
-                        }
-                    }
-                }
-            },
+        let mut profiles: Vec<SavegameManagerProfile> = match profile::load_profiles(DATA_FILE) {
+            Ok(profiles) => profiles,
             Err(err) => {
-                match err.kind() {
-                    std::io::ErrorKind::NotFound => { println!("No config file found"); },
-                    std::io::ErrorKind::PermissionDenied => { nwg::modal_error_message(&self.window, "Config file error", "Unable to open config file. Permission was denied."); },
-                    e => { nwg::modal_error_message(&self.window, "Config file error", format!("An unusual error occured trying to open config file. {:?}", e).as_str()); },
+                match err.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                    Some(std::io::ErrorKind::NotFound) => { println!("No config file found"); },
+                    Some(std::io::ErrorKind::PermissionDenied) => { nwg::modal_error_message(&self.window, "Config file error", "Unable to open config file. Permission was denied."); },
+                    Some(e) => { nwg::modal_error_message(&self.window, "Config file error", format!("An unusual error occured trying to open config file. {:?}", e).as_str()); },
+                    None => { nwg::modal_error_message(&self.window, "Config file error", format!("{}", err).as_str()); },
                 }
                 vec![]
             }
@@ -601,6 +768,13 @@ impl SavegameManagerApp {
             profiles.push(p);
         }
 
+        // Leftovers of backups interrupted by a crash
+        for profile in &profiles {
+            for name in backup::clean_up_staging(&profile.dst_path) {
+                println!("Removed incomplete backup {} of profile {}", name, profile.name);
+            }
+        }
+
         let mut selected_index: Option<usize> = None;
         for (i, profile) in profiles.iter().enumerate() {
             if profile.selected {
@@ -646,9 +820,8 @@ impl SavegameManagerApp {
                         drop(profile);
                         *self.profiles_changed.borrow_mut() = true;
 
-                        if button == &self.source_button {
-                            self.start_watcher();
-                        } else {
+                        self.sync_engine_profile();
+                        if button == &self.dest_button {
                             self.refresh_backup_list();
                         }
                     }
@@ -663,7 +836,9 @@ impl SavegameManagerApp {
             nwg::CheckBoxState::Unchecked => false,
             _ => true,
         };
+        drop(profile);
         *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
     }
 
     fn manual_save_checkbox_click(&self) {
@@ -672,7 +847,9 @@ impl SavegameManagerApp {
             nwg::CheckBoxState::Unchecked => false,
             _ => true,
         };
+        drop(profile);
         *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
     }
 
     fn show_details(&self) {
@@ -690,16 +867,18 @@ impl SavegameManagerApp {
 
                 self.savegame_detail_name_content.set_text(savegame.name.as_str());
                 self.savegame_detail_date_content.set_text(local_datetime_from_millis(savegame.date).format("%c").to_string().as_str());
-                self.savegame_detail_checksums_content.set_text(savegame.checksums.iter().map(|c| {
-                    let file_name = if c.0.len() > 21 { format!("{}…", &c.0[..20]) } else { format!("{}", c.0) };
+                self.savegame_detail_tags_content.set_text(savegame.tags.join(", ").as_str());
+                self.savegame_detail_notes_content.set_text(savegame.notes.replace("\n", "\r\n").as_str());
+                self.savegame_detail_files.set_collection(savegame.checksums.iter().map(|c| {
+                    let file_name = if c.0.chars().count() > 21 { format!("{}…", c.0.chars().take(20).collect::<String>()) } else { format!("{}", c.0) };
                     String::from(&format!("{}… / {}", &c.1[..15], file_name))
-                }).collect::<Vec<String>>().join("\r\n").as_str());
+                }).collect::<Vec<String>>());
+                self.savegame_load_files.set_enabled(false);
 
-                let dst_path = PathBuf::from(self.get_current_profile().dst_path.clone()).join(&savegame.name).join("screenshot.jpg");
-                if dst_path.exists() && dst_path.is_file() {
+                if let Some(screenshot_file) = backup::screenshot_path(&self.get_current_profile().dst_path, &savegame.name) {
                     let mut screenshot = nwg::Bitmap::default();
                     nwg::Bitmap::builder()
-                        .source_file(Some(dst_path.to_str().unwrap_or_default()))
+                        .source_file(Some(screenshot_file.path().to_str().unwrap_or_default()))
                         .size(Some((295, 166)))
                         .strict(false)
                         .build(&mut screenshot).unwrap_or_default();
@@ -711,6 +890,11 @@ impl SavegameManagerApp {
                 self.savegame_load.set_enabled(true);
                 self.savegame_rename.set_enabled(true);
                 self.savegame_delete.set_enabled(true);
+                self.savegame_verify.set_enabled(true);
+                self.savegame_pin.set_enabled(true);
+                self.savegame_pin.set_text(if savegame.pinned { "Unpin" } else { "Pin" });
+                self.savegame_save_annotations.set_enabled(true);
+                self.savegame_compare.set_enabled(true);
             },
             None => {
                 if last_backup.is_none() {
@@ -721,12 +905,20 @@ impl SavegameManagerApp {
 
                 self.savegame_detail_name_content.set_text("-");
                 self.savegame_detail_date_content.set_text("-");
-                self.savegame_detail_checksums_content.set_text("-");
+                self.savegame_detail_tags_content.set_text("");
+                self.savegame_detail_notes_content.set_text("");
+                self.savegame_detail_files.set_collection(vec![]);
+                self.savegame_load_files.set_enabled(false);
                 self.savegame_detail_screenshot.set_bitmap(Some(&self.no_screenshot));
 
                 self.savegame_load.set_enabled(false);
                 self.savegame_rename.set_enabled(false);
                 self.savegame_delete.set_enabled(false);
+                self.savegame_verify.set_enabled(false);
+                self.savegame_pin.set_enabled(false);
+                self.savegame_pin.set_text("Pin");
+                self.savegame_save_annotations.set_enabled(false);
+                self.savegame_compare.set_enabled(false);
 
                 self.rename_dialog.set_visible(false);
             }
@@ -735,9 +927,9 @@ impl SavegameManagerApp {
 
     fn open_screenshot(&self) {
         if let Some(savegame) = self.savegame_list.get_selected_savegame() {
-            let dst_path = PathBuf::from(self.get_current_profile().dst_path.clone()).join(&savegame.name).join("screenshot.jpg");
-            if dst_path.exists() && dst_path.is_file() {
-                let _ = opener::open(dst_path);
+            if let Some(screenshot_file) = backup::screenshot_path(&self.get_current_profile().dst_path, &savegame.name) {
+                // The viewer reads the file after this returns
+                let _ = opener::open(screenshot_file.keep());
             }
         }
     }
@@ -753,25 +945,43 @@ impl SavegameManagerApp {
             nwg::EventData::OnKey(nwg::keys::DELETE) => {
                 self.delete_click();
             }
+            nwg::EventData::OnKey(nwg::keys::_P) => {
+                self.pin_click();
+            }
             _ => {}
         }
     }
 
     fn load_click(&self) {
         if let Some(savegame) = self.savegame_list.get_selected_savegame() {
-            write_to_rwlock(&WATCHER_PAUSED, true);
-            let data = self.get_current_profile();
-            let src_path = data.src_path.clone();
-            let dst_path = data.dst_path.clone();
-            
-            if let Err(err) = backup::load_backup(&src_path, &dst_path, &savegame) {
+            let result = self.engine.borrow().restore(&savegame);
+            if let Err(err) = result {
                 println!("Error loading backup: {:?}", err);
                 nwg::modal_error_message(&self.window, "Load error", format!("Error loading backup: {}", err).as_str());
             }
 
             self.refresh_backup_list();
             self.savegame_list.select_by_name(savegame.name.as_str());
-            write_to_rwlock(&WATCHER_PAUSED, false);
+        }
+    }
+
+    fn undo_load_click(&self) {
+        let result = nwg::modal_message(&self.window, &nwg::MessageParams { title: "Undo load", content: "Do you want to put your savegame back into the state it was in before the last backup was loaded?", buttons: nwg::MessageButtons::YesNo, icons: nwg::MessageIcons::Question });
+        match result {
+            nwg::MessageChoice::Yes => {
+                let result = self.engine.borrow().undo_last_restore();
+                match result {
+                    Ok(snapshot) => {
+                        self.refresh_backup_list();
+                        self.savegame_list.select_by_name(snapshot.name.as_str());
+                    },
+                    Err(err) => {
+                        println!("Error undoing load: {:?}", err);
+                        nwg::modal_error_message(&self.window, "Undo error", format!("Error undoing load: {}", err).as_str());
+                    }
+                }
+            },
+            _ => {}
         }
     }
 
@@ -783,6 +993,10 @@ impl SavegameManagerApp {
 
             *self.rename_mode.borrow_mut() = RenameMode::Backup;
             self.rename_input.set_text(savegame.name.as_str());
+            self.rename_tags.set_text(savegame.tags.join(", ").as_str());
+            self.rename_notes.set_text(savegame.notes.replace("\n", " ").as_str());
+            self.rename_tags.set_visible(true);
+            self.rename_notes.set_visible(true);
             self.rename_dialog.set_position(x + (width as i32 - dialog_width as i32) - 15, y + (height as i32 - dialog_height as i32) - 50);
             self.rename_dialog.set_text("Rename backup");
             self.rename_dialog.set_visible(true);
@@ -803,15 +1017,22 @@ impl SavegameManagerApp {
                         .replace("*", "").trim().to_owned();
         
                     if new_name.len() > 0 {
-                        match backup::rename_backup(&self.get_current_profile().dst_path, &savegame.name, &new_name) {
+                        let notes = if self.rename_notes.text() == savegame.notes.replace("\n", " ") { savegame.notes.clone() } else { self.rename_notes.text().trim().to_owned() };
+                        let tags = backup::parse_tags(&self.rename_tags.text());
+                        let engine = self.engine.borrow();
+                        let result = engine.rename_backup(&savegame.name, &new_name)
+                            .map_err(|err| format!("Error renaming backup: {}", err))
+                            .and_then(|_| engine.set_annotations(&new_name, &notes, &tags).map(|_| ()).map_err(|err| format!("Error saving tags and notes: {}", err)));
+                        drop(engine);
+                        match result {
                             Ok(_) => {
                                 self.rename_dialog.set_visible(false);
                                 self.refresh_backup_list();
                                 self.savegame_list.select_by_name(new_name.as_str());
                             },
-                            Err(err) => {
-                                println!("Error renaming backup: {:?}", err);
-                                nwg::modal_error_message(&self.rename_dialog, "Rename error", format!("Error renaming backup: {}", err).as_str());
+                            Err(message) => {
+                                println!("{}", message);
+                                nwg::modal_error_message(&self.rename_dialog, "Rename error", message.as_str());
                             }
                         }
                     } else {
@@ -855,7 +1076,8 @@ impl SavegameManagerApp {
             let result = nwg::modal_message(&self.window, &nwg::MessageParams { title: "Deleting backup", content: format!("Are you sure you want to delete {}?\n(We'll just move it to the recycle bin for your.)", savegame.name).as_str(), buttons: nwg::MessageButtons::YesNo, icons: nwg::MessageIcons::Question });
             match result {
                 nwg::MessageChoice::Yes => {
-                    match crate::backup::recycle_backup(&self.get_current_profile().dst_path, &savegame.name) {
+                    let result = self.engine.borrow().recycle_backup(&savegame.name);
+                    match result {
                         Ok(_) => {
                             self.refresh_backup_list();
                         },
@@ -870,6 +1092,233 @@ impl SavegameManagerApp {
         }
     }
 
+    fn save_annotations_click(&self) {
+        if let Some(savegame) = self.savegame_list.get_selected_savegame() {
+            let notes = self.savegame_detail_notes_content.text().replace("\r\n", "\n").trim().to_owned();
+            let tags = backup::parse_tags(&self.savegame_detail_tags_content.text());
+            let result = self.engine.borrow().set_annotations(&savegame.name, &notes, &tags);
+            match result {
+                Ok(meta) => {
+                    self.refresh_backup_list();
+                    self.savegame_list.select_by_name(meta.name.as_str());
+                },
+                Err(err) => {
+                    println!("Error saving tags and notes: {:?}", err);
+                    nwg::modal_error_message(&self.window, "Save error", format!("Error saving tags and notes: {}", err).as_str());
+                }
+            }
+        }
+    }
+
+    /// Offers to rebuild the meta files of backups that are missing from the list because
+    /// their meta file is missing or broken, including folders copied in by hand.
+    fn repair_click(&self) {
+        let broken = self.engine.borrow().broken_backups();
+        let broken = match broken {
+            Ok(broken) => broken,
+            Err(err) => {
+                nwg::modal_error_message(&self.window, "Repair error", format!("Error looking for broken backups: {}", err).as_str());
+                return;
+            }
+        };
+        if broken.is_empty() {
+            nwg::modal_info_message(&self.window, "Repair backups", "Every backup has a readable meta file.");
+            return;
+        }
+
+        let names: Vec<String> = broken.iter().map(|b| format!("{}: {}", b.name, b.error)).collect();
+        let content = format!("These backups can't be read:\n{}\n\nDo you want to rebuild their meta files from the backed up files?", names.join("\n"));
+        let result = nwg::modal_message(&self.window, &nwg::MessageParams { title: "Repair backups", content: content.as_str(), buttons: nwg::MessageButtons::YesNo, icons: nwg::MessageIcons::Question });
+        if !matches!(result, nwg::MessageChoice::Yes) {
+            return;
+        }
+
+        let result = self.engine.borrow().repair_backups();
+        self.refresh_backup_list();
+        match result {
+            Ok(report) => {
+                let mut content = format!("Repaired {} backup(s).", report.repaired.len());
+                for failed in &report.failed {
+                    content.push_str(format!("\n{} could not be repaired: {}", failed.name, failed.error).as_str());
+                }
+                if report.failed.is_empty() {
+                    nwg::modal_info_message(&self.window, "Repair backups", content.as_str());
+                } else {
+                    nwg::modal_error_message(&self.window, "Repair backups", content.as_str());
+                }
+            },
+            Err(err) => {
+                println!("Error repairing backups: {:?}", err);
+                nwg::modal_error_message(&self.window, "Repair error", format!("Error repairing backups: {}", err).as_str());
+            }
+        }
+    }
+
+    /// Compares the selected backup with the live files, or two selected backups with each other.
+    fn compare_click(&self) {
+        let mut selected = self.savegame_list.get_selected_savegames();
+        selected.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let result = match selected.as_slice() {
+            [backup] => {
+                *self.compared_backups.borrow_mut() = Some((backup.clone(), None));
+                self.engine.borrow().diff_with_live(backup)
+            },
+            [old, new] => {
+                *self.compared_backups.borrow_mut() = Some((old.clone(), Some(new.clone())));
+                self.engine.borrow().diff_backups(old, new)
+            },
+            _ => {
+                nwg::modal_info_message(&self.window, "Compare backups", "Select one backup to compare it with the live files or two backups to compare them with each other.");
+                return;
+            }
+        };
+
+        match result {
+            Ok(comparison) => self.show_diff(&comparison),
+            Err(err) => {
+                println!("Error comparing backups: {:?}", err);
+                nwg::modal_error_message(&self.window, "Compare error", format!("Error comparing backups: {}", err).as_str());
+            }
+        }
+    }
+
+    fn show_diff(&self, comparison: &diff::BackupDiff) {
+        let new_name = comparison.new.clone().unwrap_or(String::from("live files"));
+        self.diff_dialog.set_text(format!("Compare {} with {}", comparison.old, new_name).as_str());
+        self.diff_summary.set_text(format!("{} (double-click a file to see the changed bytes)", comparison.summary()).as_str());
+
+        let size = |version: &Option<diff::FileVersion>| version.as_ref().and_then(|v| v.size).map(diff::format_size).unwrap_or_default();
+        let modified = |version: &Option<diff::FileVersion>| version.as_ref().and_then(|v| v.modified).map(|m| local_datetime_from_millis(m).format("%x %X").to_string()).unwrap_or_default();
+
+        self.diff_list.set_redraw(false);
+        self.diff_list.clear();
+        // Changes first, unchanged files at the end
+        let files = comparison.files.iter().filter(|f| f.change != diff::FileChange::Unchanged).chain(comparison.files.iter().filter(|f| f.change == diff::FileChange::Unchanged));
+        let mut compared_files = self.compared_files.borrow_mut();
+        compared_files.clear();
+        for (index, file) in files.enumerate() {
+            compared_files.push(file.path.clone());
+            self.diff_list.insert_item(nwg::InsertListViewItem { column_index: 0, index: Some(index as i32), text: Some(file.path.clone()), image: None });
+            let cells = [file.change.to_string(), size(&file.old), size(&file.new), modified(&file.old), modified(&file.new)];
+            for (column, text) in cells.into_iter().enumerate() {
+                self.diff_list.update_item(index, nwg::InsertListViewItem { column_index: column as i32 + 1, index: Some(index as i32), text: Some(text), image: None });
+            }
+        }
+        self.diff_list.set_redraw(true);
+
+        let (x, y) = self.window.position();
+        self.diff_dialog.set_position(x + 50, y + 50);
+        self.diff_dialog.set_visible(true);
+        self.diff_list.set_focus();
+    }
+
+    /// Shows the changed bytes of the file double-clicked in the diff dialog.
+    fn diff_file_open(&self) {
+        let file = match self.diff_list.selected_item().and_then(|index| self.compared_files.borrow().get(index).cloned()) {
+            Some(file) => file,
+            None => return,
+        };
+        let compared = self.compared_backups.borrow().clone();
+        let (old, new) = match compared {
+            Some(compared) => compared,
+            None => return,
+        };
+
+        let options = hexdiff::HexDiffOptions::default();
+        let result = match &new {
+            Some(new) => self.engine.borrow().hex_diff_backups(&old, new, &file, &options),
+            None => self.engine.borrow().hex_diff_with_live(&old, &file, &options),
+        };
+        match result {
+            Ok(comparison) => {
+                self.hex_dialog.set_text(format!("{}: {} / {}", file, old.name, new.map(|n| n.name).unwrap_or(String::from("live files"))).as_str());
+                self.hex_view.set_text(comparison.to_string().replace("\n", "\r\n").as_str());
+                let (x, y) = self.diff_dialog.position();
+                self.hex_dialog.set_position(x + 30, y + 30);
+                self.hex_dialog.set_visible(true);
+                self.hex_view.set_focus();
+            },
+            Err(err) => {
+                println!("Error comparing file: {:?}", err);
+                nwg::modal_error_message(&self.diff_dialog, "Compare error", format!("Error comparing {}: {}", file, err).as_str());
+            }
+        }
+    }
+
+    fn hex_close(&self) {
+        self.hex_dialog.set_visible(false);
+    }
+
+    fn diff_close(&self) {
+        self.diff_dialog.set_visible(false);
+    }
+
+    fn diff_key_released(&self, event: &nwg::EventData) {
+        if let nwg::EventData::OnKey(nwg::keys::ESCAPE) = event {
+            self.diff_close();
+        }
+    }
+
+    fn file_select_change(&self) {
+        self.savegame_load_files.set_enabled(!self.savegame_detail_files.multi_selection().is_empty());
+    }
+
+    /// Restores only the files selected in the detail pane and leaves the other live files alone.
+    fn load_files_click(&self) {
+        if let Some(savegame) = self.savegame_list.get_selected_savegame() {
+            let files: Vec<String> = self.savegame_detail_files.multi_selection().into_iter().filter_map(|index| savegame.checksums.get(index).map(|c| c.0.clone())).collect();
+            if files.is_empty() {
+                return;
+            }
+
+            let result = self.engine.borrow().restore_files(&savegame, &files);
+            if let Err(err) = result {
+                println!("Error loading files: {:?}", err);
+                nwg::modal_error_message(&self.window, "Load error", format!("Error loading files: {}", err).as_str());
+            }
+
+            self.refresh_backup_list();
+            self.savegame_list.select_by_name(savegame.name.as_str());
+        }
+    }
+
+    fn pin_click(&self) {
+        if let Some(savegame) = self.savegame_list.get_selected_savegame() {
+            let result = self.engine.borrow().set_pinned(&savegame.name, !savegame.pinned);
+            match result {
+                Ok(meta) => {
+                    self.refresh_backup_list();
+                    self.savegame_list.select_by_name(meta.name.as_str());
+                    self.savegame_pin.set_text(if meta.pinned { "Unpin" } else { "Pin" });
+                },
+                Err(err) => {
+                    println!("Error pinning backup: {:?}", err);
+                    nwg::modal_error_message(&self.window, "Pin error", format!("Error pinning backup: {}", err).as_str());
+                }
+            }
+        }
+    }
+
+    fn verify_click(&self) {
+        if let Some(savegame) = self.savegame_list.get_selected_savegame() {
+            let result = self.engine.borrow().verify_backup(&savegame);
+            match result {
+                Ok(report) => {
+                    if report.is_ok() {
+                        nwg::modal_info_message(&self.window, "Verify backup", format!("{}", report).as_str());
+                    } else {
+                        nwg::modal_error_message(&self.window, "Verify backup", format!("{} is damaged:\n{}", savegame.name, report).as_str());
+                    }
+                },
+                Err(err) => {
+                    println!("Error verifying backup: {:?}", err);
+                    nwg::modal_error_message(&self.window, "Verify error", format!("Error verifying backup: {}", err).as_str());
+                }
+            }
+        }
+    }
+
     fn profile_select_change(&self) {
         let mut profiles = self.profile_select.collection_mut();
         for profile in &mut *profiles {
@@ -881,6 +1330,14 @@ impl SavegameManagerApp {
         let mut autosave_amount = String::new();
         let mut autosave_interval = String::new();
         let mut autosave_interval_unit = ProfileIntervalUnit::Minutes;
+        let mut storage_mode = StorageMode::Folder;
+        let mut retention = RetentionPolicy::default();
+        let mut watcher_backend = WatcherBackend::Native;
+        let mut poll_interval = String::new();
+        let mut quiet_period = String::new();
+        let mut stable_polls = String::new();
+        let mut include_patterns = String::new();
+        let mut exclude_patterns = String::new();
         if let Some(selection) = selection {
             profiles[selection].selected = true;
 
@@ -892,9 +1349,19 @@ impl SavegameManagerApp {
             autosave_amount = format!("{}", profiles[selection].auto_saves_max);
             autosave_interval = format!("{}", profiles[selection].auto_saves_interval);
             autosave_interval_unit = profiles[selection].auto_saves_interval_unit.clone();
+            storage_mode = profiles[selection].storage_mode;
+            retention = profiles[selection].retention.clone();
+            watcher_backend = profiles[selection].watcher_backend;
+            poll_interval = format!("{}", profiles[selection].poll_interval_millis);
+            quiet_period = format!("{}", profiles[selection].quiet_period_millis);
+            stable_polls = format!("{}", profiles[selection].stable_polls);
+            include_patterns = profiles[selection].include_patterns.join(", ");
+            exclude_patterns = profiles[selection].exclude_patterns.join(", ");
         }
         drop(profiles);
 
+        *self.engine.borrow_mut() = BackupEngine::new(self.get_current_profile().clone());
+
         self.autosave_amount.set_text(autosave_amount.as_str());
         self.autosave_interval.set_text(autosave_interval.as_str());
         self.autosave_interval_unit.set_selection(Some(match autosave_interval_unit {
@@ -902,8 +1369,36 @@ impl SavegameManagerApp {
             ProfileIntervalUnit::Minutes => 1,
             ProfileIntervalUnit::Hours => 2,
         }));
+        self.stability_quiet_period.set_text(quiet_period.as_str());
+        self.stability_polls.set_text(stable_polls.as_str());
+        self.storage_mode.set_selection(Some(match storage_mode {
+            StorageMode::Folder => 0,
+            StorageMode::Deduplicated => 1,
+            StorageMode::Zip => 2,
+            StorageMode::TarZst => 3,
+        }));
+        self.watcher_backend.set_selection(Some(match watcher_backend {
+            WatcherBackend::Native => 0,
+            WatcherBackend::Polling => 1,
+            WatcherBackend::Hybrid => 2,
+        }));
+        self.watcher_poll_interval.set_text(poll_interval.as_str());
+        self.retention_check.set_check_state(if retention.enabled { nwg::CheckBoxState::Checked } else { nwg::CheckBoxState::Unchecked });
+        self.retention_keep_all.set_text(format!("{}", retention.keep_all_minutes).as_str());
+        self.retention_hourly.set_text(format!("{}", retention.hourly_hours).as_str());
+        self.retention_daily.set_text(format!("{}", retention.daily_days).as_str());
+        self.retention_weekly.set_text(format!("{}", retention.weekly_weeks).as_str());
+        self.retention_monthly.set_text(format!("{}", retention.monthly_months).as_str());
+        self.retention_max_count.set_text(format!("{}", retention.max_count).as_str());
+        self.retention_max_size.set_text(format!("{}", retention.max_size_mb).as_str());
+        self.set_retention_inputs_enabled();
+        self.include_patterns.set_text(include_patterns.as_str());
+        self.exclude_patterns.set_text(exclude_patterns.as_str());
 
         *self.profiles_changed.borrow_mut() = true;
+        // The new engine already has the values just put into the text inputs
+        *self.engine_profile_pending.borrow_mut() = false;
+        self.retention_report.set_text("");
         self.rename_dialog.set_visible(false);
         self.start_watcher();
         self.refresh_backup_list();
@@ -948,6 +1443,8 @@ impl SavegameManagerApp {
 
         *self.rename_mode.borrow_mut() = RenameMode::Profile;
         self.rename_input.set_text(profile.name.as_str());
+        self.rename_tags.set_visible(false);
+        self.rename_notes.set_visible(false);
         self.rename_dialog.set_position(x + (width as i32 - dialog_width as i32) - 15, y + 50);
         self.rename_dialog.set_text("Rename profile");
         self.rename_dialog.set_visible(true);
@@ -983,18 +1480,121 @@ impl SavegameManagerApp {
         } else if handle == &self.autosave_interval {
             profile.auto_saves_interval = self.autosave_interval.text().parse().unwrap_or(0);
         }
+        drop(profile);
         *self.profiles_changed.borrow_mut() = true;
+        *self.engine_profile_pending.borrow_mut() = true;
+    }
+
+    fn stability_text_input(&self, handle: &nwg::ControlHandle) {
+        let mut profile = self.get_current_profile_mut();
+        if handle == &self.stability_quiet_period {
+            profile.quiet_period_millis = self.stability_quiet_period.text().parse().unwrap_or(0);
+        } else if handle == &self.stability_polls {
+            profile.stable_polls = self.stability_polls.text().parse().unwrap_or(0);
+        }
+        drop(profile);
+        *self.profiles_changed.borrow_mut() = true;
+        *self.engine_profile_pending.borrow_mut() = true;
     }
 
     fn interval_unit_select_change(&self) {
         let mut profile = self.get_current_profile_mut();
         let collection = self.autosave_interval_unit.collection();
         profile.auto_saves_interval_unit = collection[self.autosave_interval_unit.selection().unwrap_or(0)].clone();
+        drop(profile);
+        drop(collection);
         *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
+    }
+
+    fn retention_checkbox_click(&self) {
+        let mut profile = self.get_current_profile_mut();
+        profile.retention.enabled = match self.retention_check.check_state() {
+            nwg::CheckBoxState::Unchecked => false,
+            _ => true,
+        };
+        drop(profile);
+        self.set_retention_inputs_enabled();
+        *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
+    }
+
+    fn retention_text_input(&self, handle: &nwg::ControlHandle) {
+        let mut profile = self.get_current_profile_mut();
+        if handle == &self.retention_keep_all {
+            profile.retention.keep_all_minutes = self.retention_keep_all.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_hourly {
+            profile.retention.hourly_hours = self.retention_hourly.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_daily {
+            profile.retention.daily_days = self.retention_daily.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_weekly {
+            profile.retention.weekly_weeks = self.retention_weekly.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_monthly {
+            profile.retention.monthly_months = self.retention_monthly.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_max_count {
+            profile.retention.max_count = self.retention_max_count.text().parse().unwrap_or(0);
+        } else if handle == &self.retention_max_size {
+            profile.retention.max_size_mb = self.retention_max_size.text().parse().unwrap_or(0);
+        }
+        drop(profile);
+        *self.profiles_changed.borrow_mut() = true;
+        *self.engine_profile_pending.borrow_mut() = true;
+    }
+
+    fn set_retention_inputs_enabled(&self) {
+        let enabled = self.get_current_profile().retention.enabled;
+        self.retention_keep_all.set_enabled(enabled);
+        self.retention_hourly.set_enabled(enabled);
+        self.retention_daily.set_enabled(enabled);
+        self.retention_weekly.set_enabled(enabled);
+        self.retention_monthly.set_enabled(enabled);
+        self.retention_max_count.set_enabled(enabled);
+        self.retention_max_size.set_enabled(enabled);
+    }
+
+    fn storage_mode_select_change(&self) {
+        let mut profile = self.get_current_profile_mut();
+        let collection = self.storage_mode.collection();
+        profile.storage_mode = collection[self.storage_mode.selection().unwrap_or(0)];
+        drop(profile);
+        drop(collection);
+        *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
+    }
+
+    fn watcher_backend_select_change(&self) {
+        let mut profile = self.get_current_profile_mut();
+        let collection = self.watcher_backend.collection();
+        profile.watcher_backend = collection[self.watcher_backend.selection().unwrap_or(0)];
+        drop(profile);
+        drop(collection);
+        *self.profiles_changed.borrow_mut() = true;
+        self.sync_engine_profile();
+    }
+
+    fn poll_interval_text_input(&self) {
+        let mut profile = self.get_current_profile_mut();
+        profile.poll_interval_millis = self.watcher_poll_interval.text().parse().unwrap_or(0);
+        drop(profile);
+        *self.profiles_changed.borrow_mut() = true;
+        *self.engine_profile_pending.borrow_mut() = true;
+    }
+
+    fn patterns_text_input(&self) {
+        let mut profile = self.get_current_profile_mut();
+        profile.include_patterns = filter::parse_patterns(&self.include_patterns.text());
+        profile.exclude_patterns = filter::parse_patterns(&self.exclude_patterns.text());
+        drop(profile);
+        *self.profiles_changed.borrow_mut() = true;
+        *self.engine_profile_pending.borrow_mut() = true;
     }
 }
 
 
+fn pinned_text(pinned: bool) -> String {
+    String::from(if pinned { "📌" } else { "" })
+}
+
 #[derive(Default)]
 struct SavegameListView {
     base: nwg::ListView,
@@ -1009,21 +1609,11 @@ impl SavegameListView {
         SavegameListViewBuilder {
             list_builder: nwg::ListView::builder()
                 .list_style(nwg::ListViewStyle::Detailed)
-                .flags(nwg::ListViewFlags::VISIBLE | nwg::ListViewFlags::SINGLE_SELECTION | nwg::ListViewFlags::ALWAYS_SHOW_SELECTION)
+                .flags(nwg::ListViewFlags::VISIBLE | nwg::ListViewFlags::ALWAYS_SHOW_SELECTION)
                 .ex_flags(nwg::ListViewExFlags::FULL_ROW_SELECT | nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::AUTO_COLUMN_SIZE)
         }
     }
 
-    fn get_latest_non_temp(&self) -> Option<SavegameMeta> {
-        let data = self.data.borrow();
-        for savegame in data.iter() {
-            if !savegame.is_temp() {
-                return Some(savegame.clone());
-            }
-        }
-        None
-    }
-
     fn prepare_list(&self) {
         let mut image_list = self.image_list.borrow_mut();
         nwg::ImageList::builder()
@@ -1039,7 +1629,9 @@ impl SavegameListView {
         self.set_image_list(Some(&image_list), nwg::ListViewImageListType::Small);
 
         self.insert_column(nwg::InsertListViewColumn { index: Some(0), fmt: Some(nwg::ListViewColumnFlags::LEFT), width: Some(300), text: Some("Name".to_owned()) });
-        self.insert_column(nwg::InsertListViewColumn { index: Some(1), fmt: Some(nwg::ListViewColumnFlags::LEFT), width: Some(300), text: Some("Date".to_owned()) });
+        self.insert_column(nwg::InsertListViewColumn { index: Some(1), fmt: Some(nwg::ListViewColumnFlags::LEFT), width: Some(220), text: Some("Date".to_owned()) });
+        self.insert_column(nwg::InsertListViewColumn { index: Some(2), fmt: Some(nwg::ListViewColumnFlags::LEFT), width: Some(80), text: Some("Type".to_owned()) });
+        self.insert_column(nwg::InsertListViewColumn { index: Some(3), fmt: Some(nwg::ListViewColumnFlags::CENTER), width: Some(60), text: Some("Pinned".to_owned()) });
     }
 
     fn clear_list(&self, disable_redraw: bool) {
@@ -1067,10 +1659,14 @@ impl SavegameListView {
         let row = [
             nwg::InsertListViewItem { column_index: 0, index: Some(index as i32), text: Some(meta.name), image: Some(1) },
             nwg::InsertListViewItem { column_index: 1, index: Some(index as i32), text: Some(save_timestamp.format("%c").to_string()), image: None },
+            nwg::InsertListViewItem { column_index: 2, index: Some(index as i32), text: Some(meta.kind.to_string()), image: None },
+            nwg::InsertListViewItem { column_index: 3, index: Some(index as i32), text: Some(pinned_text(meta.pinned)), image: None },
         ];
 
         self.insert_item(row[0].clone());
         self.update_item(index, row[1].clone());
+        self.update_item(index, row[2].clone());
+        self.update_item(index, row[3].clone());
     }
 
     fn update_list(&self, disable_redraw: bool) {
@@ -1101,6 +1697,8 @@ impl SavegameListView {
 
             self.update_item(index, nwg::InsertListViewItem { column_index: 0, index: Some(index as i32), text: Some(row.name.clone()), image: Some(1) });
             self.update_item(index, nwg::InsertListViewItem { column_index: 1, index: Some(index as i32), text: Some(save_timestamp.format("%c").to_string()), image: None });
+            self.update_item(index, nwg::InsertListViewItem { column_index: 2, index: Some(index as i32), text: Some(row.kind.to_string()), image: None });
+            self.update_item(index, nwg::InsertListViewItem { column_index: 3, index: Some(index as i32), text: Some(pinned_text(row.pinned)), image: None });
 
             index += 1;
         }
@@ -1119,6 +1717,11 @@ impl SavegameListView {
         }
     }
 
+    fn get_selected_savegames(&self) -> Vec<SavegameMeta> {
+        let data = self.data.borrow();
+        self.selected_items().into_iter().filter_map(|index| data.get(index).cloned()).collect()
+    }
+
     fn check_row(&self, row: usize) {
         let data = self.data.borrow();
         for (i, savegame) in data.iter().enumerate() {